@@ -0,0 +1,110 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::hint::black_box;
+
+/// Equality whose running time does not depend on where two values differ.
+///
+/// Only the length of the compared values may leak; the contents are always
+/// compared in full.
+pub trait ConstantTimeEq {
+    fn ct_eq(&self, other: &Self) -> bool;
+}
+
+impl ConstantTimeEq for [u8] {
+    fn ct_eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+        let diff = self
+            .iter()
+            .zip(other)
+            .fold(0u8, |acc, (a, b)| black_box(acc | (a ^ b)));
+        diff == 0
+    }
+}
+
+impl<const N: usize> ConstantTimeEq for [u8; N] {
+    fn ct_eq(&self, other: &Self) -> bool {
+        self[..].ct_eq(&other[..])
+    }
+}
+
+impl ConstantTimeEq for Vec<u8> {
+    fn ct_eq(&self, other: &Self) -> bool {
+        self[..].ct_eq(&other[..])
+    }
+}
+
+impl ConstantTimeEq for str {
+    fn ct_eq(&self, other: &Self) -> bool {
+        self.as_bytes().ct_eq(other.as_bytes())
+    }
+}
+
+impl ConstantTimeEq for String {
+    fn ct_eq(&self, other: &Self) -> bool {
+        self.as_bytes().ct_eq(other.as_bytes())
+    }
+}
+
+impl<T: ConstantTimeEq + ?Sized> ConstantTimeEq for &T {
+    fn ct_eq(&self, other: &Self) -> bool {
+        (**self).ct_eq(*other)
+    }
+}
+
+/// Key adapter for secrets such as API tokens.
+///
+/// `Eq` goes through [`ConstantTimeEq`] instead of the key's own `PartialEq`,
+/// and `Hash` forwards to the key's `Hash`, which for the byte and string types
+/// above feeds every byte to the hasher without short-circuiting. The map still
+/// only compares keys that share a bucket, so bucket occupancy is not hidden.
+#[derive(Clone, Copy, Default)]
+pub struct CtKey<K>(pub K);
+
+impl<K> CtKey<K> {
+    pub fn into_inner(self) -> K {
+        self.0
+    }
+}
+
+impl<K: ConstantTimeEq> PartialEq for CtKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl<K: ConstantTimeEq> Eq for CtKey<K> {}
+
+impl<K: Hash> Hash for CtKey<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashMap;
+
+    #[test]
+    fn test_ct_eq() {
+        assert!(b"secret".ct_eq(b"secret"));
+        assert!(!b"secret".ct_eq(b"secreT"));
+        assert!(!"secret".ct_eq("secrets"));
+        assert!(String::from("token").ct_eq(&String::from("token")));
+    }
+
+    #[test]
+    fn test_ct_key_map() {
+        let mut map = HashMap::new();
+        map.insert(CtKey(String::from("tok_123")), "alice");
+        map.insert(CtKey(String::from("tok_456")), "bob");
+
+        assert_eq!(map.get(&CtKey(String::from("tok_123"))), Some(&"alice"));
+        assert_eq!(map.get(&CtKey(String::from("tok_789"))), None);
+        assert_eq!(map.remove(&CtKey(String::from("tok_456"))), Some("bob"));
+        assert_eq!(map.len(), 1);
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+use std::marker::PhantomData;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+const EMPTY: u16 = 0;
+
+/// Returned by [`CuckooFilter::insert`] when no slot could be freed for the
+/// new fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuckooFilterFull;
+
+impl fmt::Display for CuckooFilterFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cuckoo filter is full")
+    }
+}
+
+impl Error for CuckooFilterFull {}
+
+/// Approximate membership filter that, unlike a bloom filter, supports
+/// removal.
+///
+/// Every item is reduced to a 16-bit fingerprint stored in one of two
+/// candidate buckets, so `contains` can return false positives but never
+/// false negatives. Removing an item that was never inserted may remove
+/// another item that shares its fingerprint.
+///
+/// Items and fingerprints are hashed with `S`, by default a hasher with
+/// fixed keys.
+pub struct CuckooFilter<T: ?Sized, S = BuildHasherDefault<DefaultHasher>> {
+    buckets: Vec<[u16; BUCKET_SIZE]>,
+    victim: Option<(usize, u16)>,
+    items: usize,
+    rng: u64,
+    hash_builder: S,
+    _item: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash + ?Sized> CuckooFilter<T> {
+    /// Creates a filter with room for at least `capacity` items.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_hasher(capacity, Default::default())
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher> CuckooFilter<T, S> {
+    /// Creates a filter with room for at least `capacity` items, hashing
+    /// with `hash_builder`.
+    pub fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        // Inserts start failing somewhere above 95% occupancy.
+        let slots = capacity + capacity / 16;
+        let nbuckets = slots.div_ceil(BUCKET_SIZE).next_power_of_two();
+        CuckooFilter {
+            buckets: vec![[EMPTY; BUCKET_SIZE]; nbuckets],
+            victim: None,
+            items: 0,
+            rng: 0x2545_f491_4f6c_dd1d,
+            hash_builder,
+            _item: PhantomData,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket = [EMPTY; BUCKET_SIZE];
+        }
+        self.victim = None;
+        self.items = 0;
+    }
+
+    pub fn insert(&mut self, item: &T) -> Result<(), CuckooFilterFull> {
+        if self.victim.is_some() {
+            return Err(CuckooFilterFull);
+        }
+
+        let (mut idx, mut fp) = self.locate(item);
+        let alt = self.alt_index(idx, fp);
+        if self.put(idx, fp) || self.put(alt, fp) {
+            self.items += 1;
+            return Ok(());
+        }
+
+        if self.next_random() & 1 == 1 {
+            idx = alt;
+        }
+        for _ in 0..MAX_KICKS {
+            let slot = (self.next_random() % BUCKET_SIZE as u64) as usize;
+            fp = std::mem::replace(&mut self.buckets[idx][slot], fp);
+            idx = self.alt_index(idx, fp);
+            if self.put(idx, fp) {
+                self.items += 1;
+                return Ok(());
+            }
+        }
+
+        // Whatever fingerprint is still homeless is parked so it isn't lost;
+        // the filter then refuses further inserts.
+        self.victim = Some((idx, fp));
+        self.items += 1;
+        Ok(())
+    }
+
+    pub fn contains(&self, item: &T) -> bool {
+        let (idx, fp) = self.locate(item);
+        let alt = self.alt_index(idx, fp);
+        self.buckets[idx].contains(&fp)
+            || self.buckets[alt].contains(&fp)
+            || self.victim == Some((idx, fp))
+            || self.victim == Some((alt, fp))
+    }
+
+    /// Removes one copy of `item`'s fingerprint, returning whether one was
+    /// found.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let (idx, fp) = self.locate(item);
+        let alt = self.alt_index(idx, fp);
+
+        if self.victim == Some((idx, fp)) || self.victim == Some((alt, fp)) {
+            self.victim = None;
+            self.items -= 1;
+            return true;
+        }
+
+        for i in [idx, alt] {
+            if let Some(slot) = self.buckets[i].iter_mut().find(|slot| **slot == fp) {
+                *slot = EMPTY;
+                self.items -= 1;
+                self.reinsert_victim();
+                return true;
+            }
+        }
+        false
+    }
+
+    fn reinsert_victim(&mut self) {
+        if let Some((idx, fp)) = self.victim {
+            let alt = self.alt_index(idx, fp);
+            if self.put(idx, fp) || self.put(alt, fp) {
+                self.victim = None;
+            }
+        }
+    }
+
+    fn locate(&self, item: &T) -> (usize, u16) {
+        let hash = self.hash_builder.hash_one(item);
+        let fp = match (hash >> 48) as u16 {
+            EMPTY => 1,
+            fp => fp,
+        };
+        (hash as usize & self.mask(), fp)
+    }
+
+    fn alt_index(&self, idx: usize, fp: u16) -> usize {
+        (idx ^ self.hash_builder.hash_one(fp) as usize) & self.mask()
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets.len() - 1
+    }
+
+    fn put(&mut self, idx: usize, fp: u16) -> bool {
+        match self.buckets[idx].iter_mut().find(|slot| **slot == EMPTY) {
+            Some(slot) => {
+                *slot = fp;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PortableState;
+
+    #[test]
+    fn test_insert_contains_remove() {
+        let mut filter = CuckooFilter::with_capacity(1000);
+        for i in 0..1000u32 {
+            filter.insert(&i).unwrap();
+        }
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000u32).all(|i| filter.contains(&i)));
+
+        let false_positives = (1000..11_000u32).filter(|i| filter.contains(i)).count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+
+        for i in 0..500u32 {
+            assert!(filter.remove(&i));
+        }
+        assert_eq!(filter.len(), 500);
+        assert!((500..1000u32).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn test_full() {
+        let mut filter = CuckooFilter::<str>::with_capacity(8);
+        let words: Vec<String> = (0..64).map(|i| format!("word{}", i)).collect();
+        let inserted = words
+            .iter()
+            .take_while(|w| filter.insert(w).is_ok())
+            .count();
+        assert!(inserted >= filter.capacity() / 2 && inserted < 64);
+        assert!(words[..inserted].iter().all(|w| filter.contains(w)));
+        assert_eq!(filter.insert("more"), Err(CuckooFilterFull));
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        let mut filter = CuckooFilter::with_hasher(100, PortableState);
+        for i in 0..100u64 {
+            filter.insert(&i).unwrap();
+        }
+        assert!((0..100u64).all(|i| filter.contains(&i)));
+        assert!(filter.remove(&7));
+        assert_eq!(filter.len(), 99);
+        assert_eq!(filter.hasher(), &PortableState);
+    }
+}
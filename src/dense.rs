@@ -0,0 +1,304 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::mem::{self, MaybeUninit};
+
+use crate::HashMap;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Largest range a [`DenseMap`] grows to. Keys at or past it are rejected,
+/// since the map would allocate a slot for every key below them.
+pub const MAX_RANGE: usize = 1 << 24;
+
+/// Map for `usize` keys drawn from a dense range `0..range`.
+///
+/// Values live in a slab with one slot per possible key and a bitset records
+/// which slots are filled, so every operation is a direct index and the
+/// overhead is one bit per slot. Inserting a key past the end grows the
+/// range, up to [`MAX_RANGE`].
+pub struct DenseMap<V> {
+    occupied: Vec<u64>,
+    values: Vec<MaybeUninit<V>>,
+    items: usize,
+}
+
+impl<V> Default for DenseMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> DenseMap<V> {
+    pub fn new() -> Self {
+        Self::with_range(0)
+    }
+
+    /// Creates a map whose keys `0..range` can be inserted without growing.
+    ///
+    /// Panics if `range` is past [`MAX_RANGE`].
+    pub fn with_range(range: usize) -> Self {
+        assert!(range <= MAX_RANGE, "DenseMap range {} is past MAX_RANGE", range);
+        let mut map = DenseMap {
+            occupied: Vec::new(),
+            values: Vec::new(),
+            items: 0,
+        };
+        map.grow(range);
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    pub fn range(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Panics if `key` is not below [`MAX_RANGE`]; `try_insert` hands the
+    /// pair back instead.
+    pub fn insert(&mut self, key: usize, value: V) -> Option<V> {
+        match self.try_insert(key, value) {
+            Ok(old) => old,
+            Err((key, _)) => panic!("DenseMap key {} is not below MAX_RANGE", key),
+        }
+    }
+
+    /// Like `insert`, but fails for a key not below [`MAX_RANGE`], handing
+    /// the pair back.
+    pub fn try_insert(&mut self, key: usize, value: V) -> Result<Option<V>, (usize, V)> {
+        if key >= self.range() {
+            match range_for(key) {
+                Some(range) => self.grow(range.max(MAX_RANGE.min(2 * self.range()))),
+                None => return Err((key, value)),
+            }
+        }
+        if self.is_occupied(key) {
+            // SAFETY: the occupied bit says the slot is initialized.
+            let slot = unsafe { self.values[key].assume_init_mut() };
+            return Ok(Some(mem::replace(slot, value)));
+        }
+        self.values[key].write(value);
+        self.occupied[key / WORD_BITS] |= 1 << (key % WORD_BITS);
+        self.items += 1;
+        Ok(None)
+    }
+
+    pub fn get(&self, key: usize) -> Option<&V> {
+        if !self.is_occupied(key) {
+            return None;
+        }
+        // SAFETY: the occupied bit says the slot is initialized.
+        Some(unsafe { self.values[key].assume_init_ref() })
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut V> {
+        if !self.is_occupied(key) {
+            return None;
+        }
+        // SAFETY: the occupied bit says the slot is initialized.
+        Some(unsafe { self.values[key].assume_init_mut() })
+    }
+
+    pub fn contains_key(&self, key: usize) -> bool {
+        self.is_occupied(key)
+    }
+
+    pub fn remove(&mut self, key: usize) -> Option<V> {
+        if !self.is_occupied(key) {
+            return None;
+        }
+        self.occupied[key / WORD_BITS] &= !(1 << (key % WORD_BITS));
+        self.items -= 1;
+        // SAFETY: the slot was initialized and is now marked vacant, so it
+        // won't be read again.
+        Some(unsafe { self.values[key].assume_init_read() })
+    }
+
+    pub fn clear(&mut self) {
+        for word_idx in 0..self.occupied.len() {
+            let mut word = mem::take(&mut self.occupied[word_idx]);
+            while word != 0 {
+                let key = word_idx * WORD_BITS + word.trailing_zeros() as usize;
+                word &= word - 1;
+                // SAFETY: the slot was initialized and is now marked vacant.
+                unsafe { self.values[key].assume_init_drop() };
+            }
+        }
+        self.items = 0;
+    }
+
+    /// Iterates in ascending key order.
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            map: self,
+            word_idx: 0,
+            word: self.occupied.first().copied().unwrap_or(0),
+        }
+    }
+
+    fn is_occupied(&self, key: usize) -> bool {
+        key < self.range() && self.occupied[key / WORD_BITS] & (1 << (key % WORD_BITS)) != 0
+    }
+
+    fn grow(&mut self, range: usize) {
+        self.values.resize_with(range, MaybeUninit::uninit);
+        self.occupied.resize(range.div_ceil(WORD_BITS), 0);
+    }
+}
+
+// Range that holds `key`, if it is within `MAX_RANGE`.
+fn range_for(key: usize) -> Option<usize> {
+    key.checked_add(1).filter(|&range| range <= MAX_RANGE)
+}
+
+impl<V> Drop for DenseMap<V> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+pub struct Iter<'a, V> {
+    map: &'a DenseMap<V>,
+    word_idx: usize,
+    word: u64,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (usize, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.word == 0 {
+            self.word_idx += 1;
+            self.word = *self.map.occupied.get(self.word_idx)?;
+        }
+        let key = self.word_idx * WORD_BITS + self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+        // SAFETY: the bit was set in the occupied bitset.
+        Some((key, unsafe { self.map.values[key].assume_init_ref() }))
+    }
+}
+
+impl<'a, V> IntoIterator for &'a DenseMap<V> {
+    type Item = (usize, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> From<DenseMap<V>> for HashMap<usize, V> {
+    fn from(mut dense: DenseMap<V>) -> Self {
+        let mut map = HashMap::with_capacity(dense.len());
+        let keys: Vec<usize> = dense.iter().map(|(key, _)| key).collect();
+        for key in keys {
+            let value = dense.remove(key).unwrap();
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+/// Fails, handing the map back unchanged, if any key is not below
+/// [`MAX_RANGE`].
+impl<V, S> TryFrom<HashMap<usize, V, S>> for DenseMap<V> {
+    type Error = HashMap<usize, V, S>;
+
+    fn try_from(map: HashMap<usize, V, S>) -> Result<Self, Self::Error> {
+        let range = match map.keys().try_fold(0, |range, &key| Some(range_for(key)?.max(range))) {
+            Some(range) => range,
+            None => return Err(map),
+        };
+        let mut dense = DenseMap::with_range(range);
+        for (key, value) in map {
+            dense.insert(key, value);
+        }
+        Ok(dense)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_dense_map() {
+        let mut map = DenseMap::with_range(100);
+        assert_eq!(map.insert(3, "three"), None);
+        assert_eq!(map.insert(70, "seventy"), None);
+        assert_eq!(map.insert(3, "drei"), Some("three"));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(3), Some(&"drei"));
+        assert_eq!(map.get(4), None);
+        assert_eq!(map.get(1000), None);
+
+        map.insert(500, "grown");
+        assert!(map.range() > 500);
+        let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![3, 70, 500]);
+
+        assert_eq!(map.remove(70), Some("seventy"));
+        assert_eq!(map.remove(70), None);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_dense_map_drops_values() {
+        let value = Rc::new(());
+        {
+            let mut map = DenseMap::new();
+            for i in 0..10 {
+                map.insert(i, Rc::clone(&value));
+            }
+            map.remove(4);
+            assert_eq!(Rc::strong_count(&value), 10);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn test_hash_map_conversion() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.insert(i * 2, i);
+        }
+        let dense = DenseMap::try_from(map).unwrap();
+        assert_eq!(dense.len(), 20);
+        assert_eq!(dense.range(), 39);
+        assert_eq!(dense.get(38), Some(&19));
+
+        let mut map = HashMap::from(dense);
+        assert_eq!(map.len(), 20);
+        assert_eq!(map.get(&10), Some(&5));
+        assert_eq!(map.capacity_report().resizes, 1);
+
+        map.insert(usize::MAX, 0);
+        let map = DenseMap::try_from(map).err().unwrap();
+        assert_eq!(map.len(), 21);
+    }
+
+    #[test]
+    fn test_keys_past_max_range() {
+        let mut map = DenseMap::new();
+        assert_eq!(map.try_insert(usize::MAX, 1u8), Err((usize::MAX, 1)));
+        assert_eq!(map.try_insert(MAX_RANGE, 2), Err((MAX_RANGE, 2)));
+        assert_eq!(map.range(), 0);
+
+        assert_eq!(map.try_insert(MAX_RANGE - 1, 3), Ok(None));
+        assert_eq!(map.range(), MAX_RANGE);
+        assert_eq!(map.get(MAX_RANGE - 1), Some(&3));
+    }
+
+    #[test]
+    #[should_panic(expected = "not below MAX_RANGE")]
+    fn test_insert_past_max_range() {
+        DenseMap::new().insert(usize::MAX, ());
+    }
+}
@@ -0,0 +1,318 @@
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use crate::{DefaultHashBuilder, HashMap};
+
+/// A view into one key's place in a [`HashMap`], returned by
+/// [`HashMap::entry`].
+pub enum Entry<'a, K, V, S = DefaultHashBuilder> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    key: K,
+    hash: u64,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    /// Looks up `key` once, for inserting or updating in place:
+    ///
+    /// ```
+    /// let mut counts = hashmap::HashMap::new();
+    /// for word in "a b a".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(counts.get(&"a"), Some(&2));
+    /// ```
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash(&key);
+        self.entry_hashed(hash, key)
+    }
+
+    /// Calls `f` with the entry for each key in `keys`, in order. Every key
+    /// is hashed before the first lookup and the table is grown once up
+    /// front, reserving as `extend` does, so a bulk upsert doesn't pay for
+    /// either per key. A key repeated in the batch sees what its earlier
+    /// occurrence left behind:
+    ///
+    /// ```
+    /// let mut counts = hashmap::HashMap::new();
+    /// counts.entry_many("a b a".split(' '), |entry| *entry.or_insert(0) += 1);
+    /// assert_eq!(counts.get(&"a"), Some(&2));
+    /// ```
+    pub fn entry_many<I, F>(&mut self, keys: I, mut f: F)
+        where I: IntoIterator<Item = K>,
+              F: FnMut(Entry<'_, K, V, S>),
+    {
+        let batch: Vec<(u64, K)> = keys.into_iter().map(|key| (self.hash(&key), key)).collect();
+        let additional = if self.is_empty() { batch.len() } else { batch.len().div_ceil(2) };
+        self.reserve(additional);
+        for (hash, key) in batch {
+            f(self.entry_hashed(hash, key));
+        }
+    }
+
+    fn entry_hashed(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S> {
+        match self.find_hashed(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry { map: self, key, hash }),
+        }
+    }
+}
+
+impl<'a, K, V, S> Entry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Like `or_insert_with`, but `default` gets to see the key.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Like `or_insert_with`, for a `default` that can fail. If it does the
+    /// error is returned and the map is unchanged.
+    pub fn or_try_insert_with<F, E>(self, default: F) -> Result<&'a mut V, E>
+        where F: FnOnce() -> Result<V, E>,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => entry.try_insert(default),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+        where V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Runs `f` on the value if the key is present.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            vacant => vacant,
+        }
+    }
+}
+
+impl<'a, K, V, S> OccupiedEntry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.map.table.get(self.index).0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.table.get(self.index).1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.table.get_mut(self.index).1
+    }
+
+    /// Like `get_mut`, but the reference outlives the entry.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.table.get_mut(self.index).1
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_index(self.index).1
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value, growing the map if needed, without hashing the
+    /// key again.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key, hash } = self;
+        map.prepare_insert();
+        let index = map.push_new(hash, key, value);
+        &mut map.table.get_mut(index).1
+    }
+
+    /// Inserts the value `f` returns. If `f` fails the error is returned
+    /// and the map is unchanged; nothing is allocated before `f` succeeds.
+    pub fn try_insert<F, E>(self, f: F) -> Result<&'a mut V, E>
+        where F: FnOnce() -> Result<V, E>,
+    {
+        let value = f()?;
+        Ok(self.insert(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_combinators() {
+        let mut map = HashMap::new();
+        map.entry("a").or_insert(1);
+        map.entry("a").and_modify(|v| *v += 10).or_insert(100);
+        map.entry("b").and_modify(|v| *v += 10).or_insert(2);
+        *map.entry("c").or_default() += 3;
+        let len = map.entry("dddd").or_insert_with_key(|key| key.len());
+        assert_eq!(*len, 4);
+
+        assert_eq!(map.get(&"a"), Some(&11));
+        assert_eq!(map.get(&"b"), Some(&2));
+        assert_eq!(map.get(&"c"), Some(&3));
+        assert_eq!(map.entry("e").key(), &"e");
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn test_occupied_and_vacant() {
+        let mut map: HashMap<_, _> = (0..20).map(|i| (i, i * 2)).collect();
+
+        match map.entry(7) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.get(), &14);
+                assert_eq!(entry.insert(70), 14);
+                assert_eq!(entry.remove_entry(), (7, 70));
+            }
+            Entry::Vacant(_) => panic!("7 is in the map"),
+        }
+        match map.entry(7) {
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 7),
+            Entry::Occupied(_) => panic!("7 was removed"),
+        }
+        assert_eq!(map.len(), 19);
+        assert_eq!(map.get(&7), None);
+    }
+
+    #[test]
+    fn test_fallible_insert() {
+        let mut map = HashMap::new();
+        let parse = |s: &'static str| move || s.parse::<i32>();
+        assert_eq!(map.entry("a").or_try_insert_with(parse("1")), Ok(&mut 1));
+        assert_eq!(map.entry("a").or_try_insert_with(parse("not called")), Ok(&mut 1));
+        assert!(map.entry("b").or_try_insert_with(parse("x")).is_err());
+        assert_eq!(map.len(), 1);
+
+        match map.entry("c") {
+            Entry::Vacant(entry) => *entry.try_insert(parse("3")).unwrap() += 1,
+            Entry::Occupied(_) => panic!("c is not in the map"),
+        }
+        match map.entry("d") {
+            Entry::Vacant(entry) => assert!(entry.try_insert(parse("")).is_err()),
+            Entry::Occupied(_) => panic!("d is not in the map"),
+        }
+        assert_eq!(map.get(&"c"), Some(&4));
+        assert_eq!(map.get(&"d"), None);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_entry_migrates() {
+        let mut map = HashMap::new();
+        map.set_resize_policy(crate::ResizePolicy::new().incremental(4));
+        for i in 0..98 {
+            map.insert(i, i);
+        }
+        assert_eq!((map.table.slots(), map.table.positions()), (256, 256 + 128));
+
+        // Each removal takes four steps through the 128 old buckets and the
+        // entries left in them, with no inserts to drive the migration.
+        let mut removed = 0;
+        while map.table.positions() > map.table.slots() {
+            match map.entry(removed) {
+                Entry::Occupied(entry) => assert_eq!(entry.remove(), removed),
+                Entry::Vacant(_) => panic!("{} went missing", removed),
+            }
+            removed += 1;
+        }
+        assert!(removed <= 57);
+        assert!((removed..98).all(|i| map.get(&i) == Some(&i)));
+    }
+
+    #[test]
+    fn test_entry_many() {
+        let mut map: HashMap<_, _> = (0..4).map(|i| (i, i * 10)).collect();
+        let mut seen = Vec::new();
+        map.entry_many(vec![2, 7, 2, 3, 7, 7], |entry| {
+            seen.push((*entry.key(), matches!(entry, Entry::Occupied(_))));
+            *entry.or_insert(0) += 1;
+        });
+
+        // Only the first 7 is vacant; the repeats find what it inserted.
+        assert_eq!(seen, [(2, true), (7, false), (2, true), (3, true), (7, true), (7, true)]);
+        assert_eq!(map.get(&2), Some(&22));
+        assert_eq!(map.get(&3), Some(&31));
+        assert_eq!(map.get(&7), Some(&3));
+        assert_eq!(map.len(), 5);
+
+        // Removing through an entry is seen by a later repeat too.
+        map.entry_many(vec![7, 7], |entry| match entry {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 3),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 7),
+        });
+        assert_eq!(map.get(&7), None);
+
+        // Each key is hashed once, and an empty map grows once for the batch.
+        let hasher = crate::tests::CountingState::default();
+        let mut map = HashMap::with_hasher(hasher.clone());
+        map.entry_many(0..100, |entry| {
+            entry.or_insert(());
+        });
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == 100);
+        assert_eq!(map.table.slots(), map.table.slots_for(100));
+        assert_eq!(map.len(), 100);
+    }
+}
@@ -0,0 +1,203 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+
+/// A key type with a small, fixed set of values, each mapped to a distinct
+/// index in `0..COUNT`.
+///
+/// Implement it by hand or declare a fieldless enum through [`enum_key!`].
+pub trait EnumKey: Sized {
+    const COUNT: usize;
+
+    fn to_index(&self) -> usize;
+
+    /// Only called with indices in `0..COUNT`.
+    fn from_index(index: usize) -> Self;
+}
+
+/// Declares a fieldless enum and implements [`EnumKey`] for it, numbering
+/// the variants in declaration order.
+///
+/// ```
+/// hashmap::enum_key! {
+///     #[derive(Debug, PartialEq)]
+///     pub enum Color { Red, Green, Blue }
+/// }
+///
+/// let mut map = hashmap::EnumMap::new();
+/// map.insert(Color::Green, "go");
+/// assert_eq!(map.get(&Color::Green), Some(&"go"));
+/// ```
+#[macro_export]
+macro_rules! enum_key {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variant:ident),* $(,)? }) => {
+        $(#[$meta])*
+        $vis enum $name {
+            $($variant),*
+        }
+
+        impl $crate::EnumKey for $name {
+            const COUNT: usize = [$(stringify!($variant)),*].len();
+
+            #[allow(unused_assignments)]
+            fn to_index(&self) -> usize {
+                let mut index = 0;
+                $(
+                    if let $name::$variant = self {
+                        return index;
+                    }
+                    index += 1;
+                )*
+                unreachable!()
+            }
+
+            #[allow(unused_assignments)]
+            fn from_index(target: usize) -> Self {
+                let mut index = 0;
+                $(
+                    if index == target {
+                        return $name::$variant;
+                    }
+                    index += 1;
+                )*
+                panic!("{} has no variant with index {}", stringify!($name), target)
+            }
+        }
+    };
+}
+
+/// Map keyed by an [`EnumKey`], stored as one slot per possible key so no
+/// hashing or probing is needed.
+pub struct EnumMap<K: EnumKey, V> {
+    // Always `K::COUNT` long; stable Rust can't size an array by an
+    // associated const of a generic parameter.
+    slots: Box<[Option<V>]>,
+    items: usize,
+    _key: PhantomData<K>,
+}
+
+impl<K: EnumKey, V> Default for EnumMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: EnumKey, V> EnumMap<K, V> {
+    pub fn new() -> Self {
+        EnumMap {
+            slots: (0..K::COUNT).map(|_| None).collect(),
+            items: 0,
+            _key: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.slots[key.to_index()].replace(value);
+        if old.is_none() {
+            self.items += 1;
+        }
+        old
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.slots[key.to_index()].as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.slots[key.to_index()].as_mut()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.slots[key.to_index()].is_some()
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.slots[key.to_index()].take();
+        if old.is_some() {
+            self.items -= 1;
+        }
+        old
+    }
+
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = None;
+        }
+        self.items = 0;
+    }
+
+    /// Iterates in key index order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.iter().enumerate(),
+            _key: PhantomData,
+        }
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    slots: core::iter::Enumerate<core::slice::Iter<'a, Option<V>>>,
+    _key: PhantomData<K>,
+}
+
+impl<'a, K: EnumKey, V> Iterator for Iter<'a, K, V> {
+    type Item = (K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .by_ref()
+            .find_map(|(i, slot)| slot.as_ref().map(|v| (K::from_index(i), v)))
+    }
+}
+
+impl<'a, K: EnumKey, V> IntoIterator for &'a EnumMap<K, V> {
+    type Item = (K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    enum_key! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Dir { North, East, South, West }
+    }
+
+    #[test]
+    fn test_enum_key() {
+        assert_eq!(Dir::COUNT, 4);
+        assert_eq!(Dir::South.to_index(), 2);
+        assert_eq!(Dir::from_index(3), Dir::West);
+    }
+
+    #[test]
+    fn test_enum_map() {
+        let mut map = EnumMap::new();
+        assert_eq!(map.insert(Dir::East, 1), None);
+        assert_eq!(map.insert(Dir::North, 2), None);
+        assert_eq!(map.insert(Dir::East, 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert!(map.contains_key(&Dir::North));
+        assert_eq!(map.get(&Dir::West), None);
+
+        *map.get_mut(&Dir::North).unwrap() += 10;
+        let entries: Vec<_> = map.iter().collect();
+        assert_eq!(entries, vec![(Dir::North, &12), (Dir::East, &3)]);
+
+        assert_eq!(map.remove(&Dir::East), Some(3));
+        assert_eq!(map.remove(&Dir::East), None);
+        assert_eq!(map.len(), 1);
+    }
+}
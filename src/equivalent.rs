@@ -0,0 +1,47 @@
+use core::borrow::Borrow;
+
+/// Key types that lookups accept in place of `K`.
+///
+/// Everything `K` can be borrowed as qualifies automatically, like with
+/// `std`'s maps. Implement it for your own types to look up keys that
+/// `Borrow` can't express, such as a `(String, String)` key through a pair
+/// of `&str`s:
+///
+/// ```
+/// use core::hash::{Hash, Hasher};
+/// use hashmap::{Equivalent, HashMap};
+///
+/// struct Name<'a>(&'a str, &'a str);
+///
+/// impl Hash for Name<'_> {
+///     fn hash<H: Hasher>(&self, state: &mut H) {
+///         // Must hash exactly like the `(String, String)` it stands for.
+///         (self.0, self.1).hash(state);
+///     }
+/// }
+///
+/// impl Equivalent<(String, String)> for Name<'_> {
+///     fn equivalent(&self, key: &(String, String)) -> bool {
+///         self.0 == key.0 && self.1 == key.1
+///     }
+/// }
+///
+/// let mut ages = HashMap::new();
+/// ages.insert((String::from("Ada"), String::from("Lovelace")), 36);
+/// assert_eq!(ages.get(&Name("Ada", "Lovelace")), Some(&36));
+/// ```
+///
+/// An implementation has to agree with `K`'s `Hash` and `Eq`: equivalent
+/// values must hash the same as the key they match.
+pub trait Equivalent<K: ?Sized> {
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q, K> Equivalent<K> for Q
+    where Q: Eq + ?Sized,
+          K: Borrow<Q> + ?Sized,
+{
+    fn equivalent(&self, key: &K) -> bool {
+        self == key.borrow()
+    }
+}
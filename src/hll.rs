@@ -0,0 +1,159 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash};
+use std::marker::PhantomData;
+
+const MIN_PRECISION: u8 = 4;
+const MAX_PRECISION: u8 = 18;
+
+/// Returned by [`HyperLogLog::merge`] when the two sketches use different
+/// precisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionMismatch;
+
+impl fmt::Display for PrecisionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("cannot merge HyperLogLog sketches of different precision")
+    }
+}
+
+impl Error for PrecisionMismatch {}
+
+/// Estimates the number of distinct items seen using `2^precision` one-byte
+/// registers. The standard error is about `1.04 / sqrt(2^precision)`.
+///
+/// Sketches can only be merged if they hash identically. The default hasher
+/// uses fixed keys, so any two default sketches qualify; with a custom
+/// `BuildHasher`, build both from the same state.
+pub struct HyperLogLog<T: ?Sized, S = BuildHasherDefault<DefaultHasher>> {
+    registers: Vec<u8>,
+    precision: u8,
+    hash_builder: S,
+    _item: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash + ?Sized> HyperLogLog<T> {
+    /// Panics if `precision` is outside `4..=18`.
+    pub fn new(precision: u8) -> Self {
+        Self::with_hasher(precision, Default::default())
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher> HyperLogLog<T, S> {
+    /// Panics if `precision` is outside `4..=18`.
+    pub fn with_hasher(precision: u8, hash_builder: S) -> Self {
+        assert!(
+            (MIN_PRECISION..=MAX_PRECISION).contains(&precision),
+            "HyperLogLog precision must be between {} and {}",
+            MIN_PRECISION,
+            MAX_PRECISION
+        );
+        HyperLogLog {
+            registers: vec![0; 1 << precision],
+            precision,
+            hash_builder,
+            _item: PhantomData,
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn insert(&mut self, item: &T) {
+        let hash = self.hash_builder.hash_one(item);
+        let idx = (hash >> (64 - self.precision)) as usize;
+        let rest = hash << self.precision;
+        let rank = (rest.leading_zeros() + 1).min(65 - self.precision as u32) as u8;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    pub fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.registers.iter().map(|&r| (-(r as f64)).exp2()).sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+
+    /// Folds `other` into this sketch, so the estimate covers the union of
+    /// both inputs.
+    pub fn merge(&mut self, other: &Self) -> Result<(), PrecisionMismatch> {
+        if self.precision != other.precision {
+            return Err(PrecisionMismatch);
+        }
+        for (mine, &theirs) in self.registers.iter_mut().zip(&other.registers) {
+            *mine = (*mine).max(theirs);
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        for r in &mut self.registers {
+            *r = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(estimate: u64, actual: u64, tolerance: f64) {
+        let error = (estimate as f64 - actual as f64).abs() / actual as f64;
+        assert!(error < tolerance, "estimate {} for {}", estimate, actual);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut hll = HyperLogLog::new(14);
+        assert_eq!(hll.estimate(), 0);
+        for i in 0..100_000u32 {
+            hll.insert(&i);
+            hll.insert(&i);
+        }
+        assert_close(hll.estimate(), 100_000, 0.03);
+
+        let mut small = HyperLogLog::<str>::new(12);
+        for word in ["a", "b", "c", "a"] {
+            small.insert(word);
+        }
+        assert_eq!(small.estimate(), 3);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = HyperLogLog::new(12);
+        let mut b = HyperLogLog::new(12);
+        for i in 0..30_000u64 {
+            a.insert(&i);
+        }
+        for i in 20_000..50_000u64 {
+            b.insert(&i);
+        }
+        a.merge(&b).unwrap();
+        assert_close(a.estimate(), 50_000, 0.05);
+
+        let c = HyperLogLog::new(10);
+        assert_eq!(a.merge(&c), Err(PrecisionMismatch));
+    }
+}
@@ -0,0 +1,288 @@
+use core::hash::{BuildHasher, Hash};
+
+use crate::raw::{RawIterMut, Scan};
+use crate::{DrainTake, HashMap, Iter};
+
+impl<K, V, S> HashMap<K, V, S> {
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.table.iter(), remaining: self.table.len() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let remaining = self.table.len();
+        IterMut { inner: self.table.iter_mut(), remaining }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+}
+
+// Like `retain`, these move an incremental resize along once, when they
+// start, so that no entry moves under the scan.
+impl<K, V, S> HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    /// Removes and yields every entry. The table keeps its allocation for
+    /// reuse; entries left unyielded when the iterator is dropped are
+    /// removed anyway.
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.migrate_step();
+        let remaining = self.table.len();
+        Drain { inner: DrainTake { map: self, cursor: 0, remaining } }
+    }
+
+    /// Lazily removes and yields the entries for which `pred` returns true,
+    /// visiting each entry once without rehashing. Entries not yet visited
+    /// when the iterator is dropped stay in the map.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, S>
+        where F: FnMut(&K, &mut V) -> bool,
+    {
+        self.migrate_step();
+        let scan = self.table.scan();
+        ExtractIf { map: self, scan, pred }
+    }
+}
+
+pub struct IterMut<'a, K, V> {
+    inner: RawIterMut<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.inner.next()?;
+        self.remaining -= 1;
+        Some((&*key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+/// Iterator returned by `HashMap::into_iter`.
+pub struct IntoIter<K, V, S> {
+    map: HashMap<K, V, S>,
+    cursor: usize,
+}
+
+impl<K, V, S> Iterator for IntoIter<K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.map.pop_entry(&mut self.cursor)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.table.len(), Some(self.map.table.len()))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for IntoIter<K, V, S> {}
+
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+/// Iterator returned by [`HashMap::drain`].
+pub struct Drain<'a, K, V, S> {
+    inner: DrainTake<'a, K, V, S>,
+}
+
+impl<'a, K, V, S> Iterator for Drain<'a, K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Drain<'a, K, V, S> {}
+
+/// Iterator returned by [`HashMap::extract_if`].
+pub struct ExtractIf<'a, K, V, F, S> {
+    map: &'a mut HashMap<K, V, S>,
+    scan: Scan,
+    pred: F,
+}
+
+impl<'a, K, V, F, S> Iterator for ExtractIf<'a, K, V, F, S>
+    where F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pred = &mut self.pred;
+        self.map.table.remove_next(&mut self.scan, |(key, value)| pred(key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.table.len()))
+    }
+}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { map: self, cursor: 0 }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrowing_iterators() {
+        let mut map: HashMap<u32, u32> = (0..50).map(|i| (i, i)).collect();
+        for value in map.values_mut() {
+            *value *= 2;
+        }
+        for (key, value) in &mut map {
+            *value += key;
+        }
+
+        assert_eq!(map.iter().len(), 50);
+        assert_eq!(map.keys().sum::<u32>(), (0..50).sum::<u32>());
+        assert_eq!(map.values().sum::<u32>(), (0..50).map(|i| 3 * i).sum::<u32>());
+        assert!(map.iter().all(|(k, v)| *v == 3 * k));
+
+        let mut keys = map.keys();
+        keys.next();
+        assert_eq!(keys.len(), 49);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let map: HashMap<String, usize> = (0..20).map(|i| (i.to_string(), i)).collect();
+        let mut iter = map.into_iter();
+        assert_eq!(iter.len(), 20);
+        iter.next();
+        assert_eq!(iter.size_hint(), (19, Some(19)));
+
+        let mut pairs: Vec<(String, usize)> = iter.collect();
+        pairs.sort_by_key(|&(_, i)| i);
+        assert_eq!(pairs.len(), 19);
+        assert!(pairs.iter().all(|(k, i)| *k == i.to_string()));
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let mut odd: Vec<u32> = map.extract_if(|k, _| k % 2 == 1).map(|(k, _)| k).collect();
+        odd.sort_unstable();
+        assert_eq!(odd, (1..100).step_by(2).collect::<Vec<_>>());
+        assert_eq!(map.len(), 50);
+
+        // Stopping early leaves the rest in place.
+        let mut calls = 0;
+        let first = map.extract_if(|_, _| {
+            calls += 1;
+            true
+        }).next();
+        assert!(first.is_some());
+        assert_eq!(calls, 1);
+        assert_eq!(map.len(), 49);
+
+        map.retain(|k, v| {
+            *v += 1;
+            k % 4 == 0
+        });
+        assert!(map.iter().all(|(k, v)| k % 4 == 0 && *v == k + 1));
+    }
+
+    #[test]
+    fn test_drain_keeps_allocation() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let nbuckets = map.table.slots();
+        let drained = map.drain();
+        assert_eq!(drained.len(), 100);
+        assert_eq!(drained.map(|(k, _)| k).sum::<u32>(), (0..100).sum::<u32>());
+
+        assert!(map.is_empty());
+        assert_eq!(map.table.slots(), nbuckets);
+        map.drain().next();
+        map.insert(1, 1);
+        assert_eq!(map.drain().count(), 1);
+        assert!(map.is_empty());
+    }
+}
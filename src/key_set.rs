@@ -0,0 +1,264 @@
+use alloc::collections::BTreeSet;
+use core::hash::{BuildHasher, Hash};
+
+use crate::{DefaultHashBuilder, Equivalent, HashMap, Keys};
+
+/// Borrowed view of a map's keys with the set operations of
+/// `std::collections::HashSet`. The operations are lazy iterators over the
+/// two maps, so no keys are copied, and the maps' value types may differ.
+///
+/// ```
+/// use hashmap::HashMap;
+///
+/// let a: HashMap<_, _> = (0..5).map(|i| (i, "a")).collect();
+/// let b: HashMap<_, _> = (3..8).map(|i| (i, 1.0)).collect();
+///
+/// let mut common: Vec<_> = a.key_set().intersection(b.key_set()).copied().collect();
+/// common.sort();
+/// assert_eq!(common, [3, 4]);
+/// ```
+pub struct KeySet<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a HashMap<K, V, S>,
+}
+
+impl<K, V, S> Clone for KeySet<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, S> Copy for KeySet<'_, K, V, S> {}
+
+impl<K, V, S> HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key_set(&self) -> KeySet<'_, K, V, S> {
+        KeySet { map: self }
+    }
+}
+
+impl<'a, K, V, S> KeySet<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.map.find(key).is_some()
+    }
+
+    pub fn iter(&self) -> Keys<'a, K, V> {
+        self.map.keys()
+    }
+
+    /// Keys in either set, each yielded once.
+    pub fn union<W>(&self, other: KeySet<'a, K, W, S>) -> Union<'a, K, V, W, S> {
+        Union { ours: self.iter(), theirs: other.difference(*self) }
+    }
+
+    /// Keys in both sets.
+    pub fn intersection<W>(&self, other: KeySet<'a, K, W, S>) -> Intersection<'a, K, V, W, S> {
+        Intersection { iter: self.iter(), other }
+    }
+
+    /// Keys in this set but not in `other`.
+    pub fn difference<W>(&self, other: KeySet<'a, K, W, S>) -> Difference<'a, K, V, W, S> {
+        Difference { iter: self.iter(), other }
+    }
+
+    /// Keys in exactly one of the two sets.
+    pub fn symmetric_difference<W>(&self, other: KeySet<'a, K, W, S>) -> SymmetricDifference<'a, K, V, W, S> {
+        SymmetricDifference { ours: self.difference(other), theirs: other.difference(*self) }
+    }
+
+    pub fn is_subset<W>(&self, other: KeySet<'a, K, W, S>) -> bool {
+        self.map.keys_subset_of(other.map)
+    }
+
+    pub fn is_superset<W>(&self, other: KeySet<'a, K, W, S>) -> bool {
+        other.is_subset(*self)
+    }
+
+    pub fn is_disjoint<W>(&self, other: KeySet<'a, K, W, S>) -> bool {
+        self.map.keys_disjoint_with(other.map)
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for KeySet<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    type Item = &'a K;
+    type IntoIter = Keys<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A set of keys to filter a map against, with
+/// [`HashMap::retain_keys_in`] and [`HashMap::remove_keys_in`].
+///
+/// Implemented for [`KeySet`], this crate's [`HashSet`](crate::HashSet),
+/// `BTreeSet` and, with the `std` feature, `std::collections::HashSet`.
+pub trait KeyFilter<K> {
+    fn key_count(&self) -> usize;
+
+    fn contains_key(&self, key: &K) -> bool;
+
+    /// Calls `f` on each key once.
+    fn for_each_key(&self, f: &mut dyn FnMut(&K));
+}
+
+impl<K, V, S> KeyFilter<K> for KeySet<'_, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn key_count(&self) -> usize {
+        self.len()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.contains(key)
+    }
+
+    fn for_each_key(&self, f: &mut dyn FnMut(&K)) {
+        self.iter().for_each(f);
+    }
+}
+
+impl<K: Ord> KeyFilter<K> for BTreeSet<K> {
+    fn key_count(&self) -> usize {
+        self.len()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.contains(key)
+    }
+
+    fn for_each_key(&self, f: &mut dyn FnMut(&K)) {
+        self.iter().for_each(f);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, S> KeyFilter<K> for std::collections::HashSet<K, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn key_count(&self) -> usize {
+        self.len()
+    }
+
+    fn contains_key(&self, key: &K) -> bool {
+        self.contains(key)
+    }
+
+    fn for_each_key(&self, f: &mut dyn FnMut(&K)) {
+        self.iter().for_each(f);
+    }
+}
+
+/// Iterator returned by [`KeySet::union`].
+pub struct Union<'a, K, V, W, S = DefaultHashBuilder> {
+    ours: Keys<'a, K, V>,
+    theirs: Difference<'a, K, W, V, S>,
+}
+
+impl<'a, K: Hash + Eq, V, W, S: BuildHasher> Iterator for Union<'a, K, V, W, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ours.next().or_else(|| self.theirs.next())
+    }
+}
+
+/// Iterator returned by [`KeySet::intersection`].
+pub struct Intersection<'a, K, V, W, S = DefaultHashBuilder> {
+    iter: Keys<'a, K, V>,
+    other: KeySet<'a, K, W, S>,
+}
+
+impl<'a, K: Hash + Eq, V, W, S: BuildHasher> Iterator for Intersection<'a, K, V, W, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|&key| other.contains(key))
+    }
+}
+
+/// Iterator returned by [`KeySet::difference`].
+pub struct Difference<'a, K, V, W, S = DefaultHashBuilder> {
+    iter: Keys<'a, K, V>,
+    other: KeySet<'a, K, W, S>,
+}
+
+impl<'a, K: Hash + Eq, V, W, S: BuildHasher> Iterator for Difference<'a, K, V, W, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let other = self.other;
+        self.iter.find(|&key| !other.contains(key))
+    }
+}
+
+/// Iterator returned by [`KeySet::symmetric_difference`].
+pub struct SymmetricDifference<'a, K, V, W, S = DefaultHashBuilder> {
+    ours: Difference<'a, K, V, W, S>,
+    theirs: Difference<'a, K, W, V, S>,
+}
+
+impl<'a, K: Hash + Eq, V, W, S: BuildHasher> Iterator for SymmetricDifference<'a, K, V, W, S> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ours.next().or_else(|| self.theirs.next())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted<'a>(keys: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut keys: Vec<u32> = keys.copied().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let a: HashMap<u32, &str> = (0..6).map(|i| (i, "a")).collect();
+        let b: HashMap<u32, ()> = (4..9).map(|i| (i, ())).collect();
+        let (a, b) = (a.key_set(), b.key_set());
+
+        assert_eq!(sorted(a.union(b)), (0..9).collect::<Vec<_>>());
+        assert_eq!(sorted(a.intersection(b)), [4, 5]);
+        assert_eq!(sorted(a.difference(b)), [0, 1, 2, 3]);
+        assert_eq!(sorted(b.difference(a)), [6, 7, 8]);
+        assert_eq!(sorted(a.symmetric_difference(b)), [0, 1, 2, 3, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_set_predicates() {
+        let small: HashMap<u32, u32> = (0..3).map(|i| (i, i)).collect();
+        let large: HashMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        let empty: HashMap<u32, u32> = HashMap::new();
+
+        assert!(small.key_set().is_subset(large.key_set()));
+        assert!(large.key_set().is_superset(small.key_set()));
+        assert!(!small.key_set().is_disjoint(large.key_set()));
+        assert!(empty.key_set().is_disjoint(large.key_set()));
+        assert!(small.key_set().contains(&2));
+        assert_eq!(large.key_set().iter().count(), 10);
+        assert_eq!(sorted(empty.key_set().union(small.key_set())), [0, 1, 2]);
+    }
+}
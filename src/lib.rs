@@ -1,176 +1,1993 @@
-use std::collections::hash_map::{DefaultHasher};
-use std::hash::{Hash, Hasher};
-use std::mem;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::mem;
+use core::ops::{Deref, DerefMut, Index};
+#[cfg(feature = "record")]
+use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+mod ct;
+#[cfg(feature = "std")]
+mod cuckoo;
+mod entry;
+mod equivalent;
+pub mod dense;
+pub mod enum_map;
+#[cfg(feature = "std")]
+mod hll;
+mod iter;
+pub mod key_set;
+pub mod ordered;
+mod portable;
+mod raw;
+mod seeded;
+#[cfg(feature = "rayon")]
+mod rayon_impl;
+#[cfg(feature = "record")]
+pub mod record;
+#[cfg(feature = "serde")]
+mod serde_impl;
+pub mod set;
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+mod slab;
+#[cfg(feature = "std")]
+mod stable;
+#[cfg(feature = "std")]
+mod static_map;
+#[cfg(feature = "std")]
+mod weak;
+
+pub use crate::ct::{ConstantTimeEq, CtKey};
+#[cfg(feature = "std")]
+pub use crate::cuckoo::{CuckooFilter, CuckooFilterFull};
+pub use crate::dense::DenseMap;
+pub use crate::entry::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::equivalent::Equivalent;
+pub use crate::enum_map::{EnumKey, EnumMap};
+#[cfg(feature = "std")]
+pub use crate::hll::{HyperLogLog, PrecisionMismatch};
+pub use crate::iter::{Drain, ExtractIf, IntoIter, IterMut, Keys, Values, ValuesMut};
+pub use crate::key_set::{KeyFilter, KeySet};
+pub use crate::ordered::OrderedHashMap;
+pub use crate::portable::{PortableHasher, PortableState};
+pub use crate::seeded::{SeededHasher, SeededState};
+#[cfg(feature = "rayon")]
+pub use crate::rayon_impl::{IntoParIter, ParIter, ParIterMut};
+pub use crate::set::HashSet;
+#[cfg(feature = "serde")]
+pub use crate::serde_impl::with_hasher;
+#[cfg(feature = "std")]
+pub use crate::sharded::{
+    ShardStats, ShardedEntry, ShardedHashMap, ShardedIter, ShardedOccupiedEntry, ShardedVacantEntry, Shards, SharedRef,
+    ValueRef, ValueRefMut,
+};
+#[cfg(feature = "std")]
+pub use crate::slab::{SlabHashMap, ValueHandle};
+#[cfg(feature = "std")]
+pub use crate::stable::StableHashMap;
+#[cfg(feature = "std")]
+pub use crate::static_map::StaticHashMap;
+#[cfg(feature = "std")]
+pub use crate::weak::WeakKeyHashMap;
+
+use crate::raw::RawTable;
 
 const INITIAL_NBUCKETS: usize = 1;
+const DEFAULT_MAX_LOAD: f64 = 0.75;
+
+/// Hasher the map types use when none is named: a randomly seeded
+/// [`SeededState`] with the `std` feature.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = SeededState;
+
+/// Hasher the map types use when none is named. Without the `std` feature
+/// there is no randomly keyed hasher to default to, so this has no values
+/// and every map is built with `with_hasher` and a hasher of your choosing.
+#[cfg(not(feature = "std"))]
+pub enum DefaultHashBuilder {}
+
+#[cfg(not(feature = "std"))]
+impl BuildHasher for DefaultHashBuilder {
+    type Hasher = PortableHasher;
+
+    fn build_hasher(&self) -> PortableHasher {
+        match *self {}
+    }
+}
+
+/// Hash map with open addressing: entries live inline in a single table of
+/// buckets, placed by Robin Hood linear probing.
+///
+/// # Allocation
+///
+/// Inserting can allocate when it grows the table. That covers `insert`, `replace`,
+/// `get_or_try_insert_with`, `reserve_slot`, `insert_scoped`, `extend`,
+/// `entry_many` and the destination side of `transfer`; [`insert_within_capacity`] is the
+/// non-allocating alternative. `entry_many` also allocates a buffer for the
+/// hashed batch. `with_capacity`, `reserve` and
+/// `shrink_to_fit` reallocate the table up front.
+///
+/// Lookups, `remove`, `swap_values`, `prune`, `retain`, `retain_keys_in`,
+/// `remove_keys_in`, `extract_if`, `drain_take`, `drain`, iteration and the
+/// [`KeySet`] operations never allocate. `iter_chunks` allocates the `Vec` it returns, and
+/// dropping the map frees everything. Collision alarms and allocation hooks
+/// run your own code, which may allocate.
+///
+/// # Borrowed keys
+///
+/// Keys may borrow from a buffer that outlives the map, such as `&str`
+/// slices of a source file. The borrow checker keeps the map from outliving
+/// that buffer:
+///
+/// ```compile_fail,E0597
+/// use hashmap::HashMap;
+///
+/// let mut map = HashMap::new();
+/// {
+///     let arena = String::from("foo bar");
+///     for ident in arena.split(' ') {
+///         map.insert(ident, ());
+///     }
+/// }
+/// assert_eq!(map.len(), 2);
+/// ```
+///
+/// [`insert_within_capacity`]: HashMap::insert_within_capacity
+pub struct HashMap<K, V, S = DefaultHashBuilder> {
+    table: RawTable<(K, V)>,
+    alarm: Option<CollisionAlarm>,
+    alloc_hook: Option<AllocHook>,
+    policy: ResizePolicy,
+    peak_len: usize,
+    resizes: usize,
+    prune_cursor: usize,
+    hash_builder: S,
+}
+
+type CollisionAlarm = Arc<dyn Fn(&CollisionReport) + Send + Sync>;
+type AllocHook = Arc<dyn Fn(&AllocEvent) + Send + Sync>;
+
+/// An allocation or deallocation made by the map itself, reported to the
+/// hook installed with [`HashMap::set_alloc_hook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocEvent {
+    pub kind: AllocKind,
+    pub bytes: usize,
+    pub reason: AllocReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocKind {
+    Alloc,
+    Dealloc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AllocReason {
+    /// The table was doubled and every entry moved.
+    Grow,
+    /// The table was reallocated smaller by `shrink_to_fit`.
+    Shrink,
+    /// The map was dropped.
+    Drop,
+}
+
+/// Passed to the collision alarm when a probe sequence grows longer than a
+/// well-distributed hash would plausibly produce for the map's size.
+/// `chain_len` is the number of buckets a lookup of the worst key visits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionReport {
+    pub chain_len: usize,
+    pub bound: usize,
+    pub len: usize,
+    pub buckets: usize,
+}
+
+/// Growth statistics over a map's lifetime, from [`HashMap::capacity_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityReport {
+    /// The largest `len()` the map has reached.
+    pub peak_len: usize,
+    /// How many times the table has been reallocated.
+    pub resizes: usize,
+    /// Capacity to pass to [`HashMap::with_capacity`] so that reaching
+    /// `peak_len` needs no resize.
+    pub suggested_capacity: usize,
+}
+
+/// When a [`HashMap`] grows and how it moves its entries across, set with
+/// [`HashMap::set_resize_policy`].
+///
+/// By default the table grows once more than 3/4 of its buckets would be
+/// full, and the insert that triggers it moves every entry at once. With
+/// [`incremental`](ResizePolicy::incremental) resizing that insert only
+/// allocates the new table; the entries follow a few buckets at a time on
+/// later inserts and removals, and lookups search both tables until they
+/// have all moved. Sizing up front with `with_capacity`, `reserve` or
+/// `extend` still resizes at once.
+///
+/// ```
+/// use hashmap::{HashMap, ResizePolicy};
+///
+/// let mut map = HashMap::new();
+/// map.set_resize_policy(ResizePolicy::new().max_load_factor(0.5).incremental(8));
+/// for i in 0..1000 {
+///     map.insert(i, i);
+/// }
+/// assert_eq!(map.get(&999), Some(&999));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResizePolicy {
+    max_load: f64,
+    step: Option<usize>,
+}
+
+impl ResizePolicy {
+    /// Grows past a load factor of 3/4, all at once.
+    pub const fn new() -> Self {
+        ResizePolicy { max_load: DEFAULT_MAX_LOAD, step: None }
+    }
+
+    /// Grows once an insert would fill more than `load` of the buckets.
+    /// Lower values keep probes shorter at the cost of memory.
+    ///
+    /// Panics unless `load` is strictly between 0 and 1.
+    pub fn max_load_factor(self, load: f64) -> Self {
+        assert!(load > 0.0 && load < 1.0, "load factor {} is not between 0 and 1", load);
+        ResizePolicy { max_load: load, ..self }
+    }
+
+    /// Resizes incrementally: each insert or removal takes at most
+    /// `buckets` steps through the old table, a step either skipping an
+    /// empty bucket or moving one entry. If inserts outpace that and the
+    /// new table fills up, the rest are moved at once; `buckets` of at
+    /// least `2 / load` (3 at the default load factor) avoids it.
+    ///
+    /// Panics if `buckets` is zero.
+    pub fn incremental(self, buckets: usize) -> Self {
+        assert!(buckets > 0, "incremental resizing needs to move at least one bucket per call");
+        ResizePolicy { step: Some(buckets), ..self }
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        self.max_load
+    }
+
+    /// Buckets moved per call when resizing incrementally.
+    pub fn incremental_step(&self) -> Option<usize> {
+        self.step
+    }
+}
+
+impl Default for ResizePolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returned when an operation needs a key that is not in the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyNotFound;
+
+impl fmt::Display for KeyNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("key not found in map")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KeyNotFound {}
+
+// At the map's load factor Robin Hood probing keeps the expected longest
+// probe around log2(len). Twice that plus a constant leaves no room for false
+// alarms.
+fn plausible_chain_bound(len: usize) -> usize {
+    let log2 = (usize::BITS - len.leading_zeros()) as usize;
+    2 * log2 + 8
+}
 
-pub struct HashMap<K, V> {
-    buckets: Vec<Vec<(K, V)>>,
-    items : usize,
+impl<K, V, S> Default for HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
 }
 
-impl<K, V> HashMap<K, V>
+#[cfg(feature = "std")]
+impl<K, V> HashMap<K, V, SeededState>
     where K: Hash + Eq,
 {
+    /// Creates an empty map hashing with a randomly seeded `SeededState`.
     pub fn new() -> Self {
+        Self::with_hasher(SeededState::new())
+    }
+
+    /// Creates an empty map that can hold `capacity` entries before it
+    /// needs to resize.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, SeededState::new())
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    /// Creates an empty map that hashes keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
         HashMap {
-            buckets: Vec::new(),
-            items: 0,
+            table: RawTable::new(),
+            alarm: None,
+            alloc_hook: None,
+            policy: ResizePolicy::new(),
+            peak_len: 0,
+            resizes: 0,
+            prune_cursor: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Changes when and how the map resizes. A new load factor applies to
+    /// `capacity` and the next resize straight away; turning incremental
+    /// resizing off finishes any resize under way.
+    pub fn set_resize_policy(&mut self, policy: ResizePolicy) {
+        self.policy = policy;
+        self.table.set_max_load(policy.max_load);
+        if policy.step.is_none() {
+            self.migrate(usize::MAX);
         }
     }
 
+    pub fn resize_policy(&self) -> ResizePolicy {
+        self.policy
+    }
+
+    /// How many entries the map holds before the next insert resizes it.
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
     pub fn len(&self) -> usize{
-        self.items
+        self.table.len()
     }
 
     pub fn is_empty(&self) -> bool{
-        self.items == 0
+        self.table.len() == 0
+    }
+
+    pub fn capacity_report(&self) -> CapacityReport {
+        CapacityReport {
+            peak_len: self.peak_len,
+            resizes: self.resizes,
+            suggested_capacity: self.peak_len,
+        }
+    }
+
+    /// Calls `alarm` whenever an insert leaves some key more than
+    /// [`CollisionReport::bound`] buckets down its probe sequence, a likely
+    /// sign of a HashDoS attempt.
+    pub fn set_collision_alarm<F>(&mut self, alarm: F)
+        where F: Fn(&CollisionReport) + Send + Sync + 'static,
+    {
+        self.alarm = Some(Arc::new(alarm));
+    }
+
+    pub fn clear_collision_alarm(&mut self) {
+        self.alarm = None;
+    }
+
+    /// Calls `hook` for every allocation and deallocation of the map's own
+    /// storage. Memory owned by keys and values is not reported.
+    pub fn set_alloc_hook<F>(&mut self, hook: F)
+        where F: Fn(&AllocEvent) + Send + Sync + 'static,
+    {
+        self.alloc_hook = Some(Arc::new(hook));
+    }
+
+    pub fn clear_alloc_hook(&mut self) {
+        self.alloc_hook = None;
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let bucket_idx = self.bucket_idx(key);
-        let bucket = &mut self.buckets[bucket_idx];
-        let i = bucket.iter().position(|&(ref ekey, _)| ekey==key)?;
-        self.items -=1;
-        return Some(bucket.swap_remove(i).1);
+    /// Scans every bucket and reports the longest probe sequence if it
+    /// exceeds the plausible bound for the current size.
+    pub fn check_collisions(&self) -> Result<(), CollisionReport> {
+        let chain_len = self.table.longest_probe();
+        let report = self.collision_report(chain_len);
+        if chain_len > report.bound {
+            return Err(report);
+        }
+        Ok(())
+    }
+
+    fn collision_report(&self, chain_len: usize) -> CollisionReport {
+        CollisionReport {
+            chain_len,
+            bound: plausible_chain_bound(self.len()),
+            len: self.len(),
+            buckets: self.table.slots(),
+        }
+    }
+
+    pub fn remove<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let index = self.find(key)?;
+        let (_, (_, value)) = self.remove_index(index);
+        Some(value)
+    }
+
+    // Removes the entry at `index`, returning it with its stored hash, and
+    // moves an incremental resize along. Removals of one looked-up entry
+    // (`remove`, `transfer`, `OccupiedEntry` and the sharded map's
+    // `remove` and entries) go through here. Passes that remove as they go
+    // (`retain`, `prune`, the drains, `extract_if`, `into_iter` and the
+    // sharded `reshard`) call the table directly instead, since a step in
+    // the middle could move entries they haven't reached behind them.
+    fn remove_index(&mut self, index: usize) -> (u64, (K, V)) {
+        let removed = self.table.take(index);
+        self.migrate_step();
+        removed
     }
 
-    pub fn get(&mut self, key: &K) -> Option<&V> {
-        let bucket_idx = self.bucket_idx(key);
-        return self.buckets[bucket_idx]
-            .iter()
-            .find(|&(ref ekey,_)| { ekey == key })
-            .map(|&(_, ref v)| v);
+    /// Looks `key` up by anything it is [`Equivalent`] to, so a
+    /// `HashMap<String, V>` can be queried with a `&str`.
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        let index = self.find(key)?;
+        Some(&self.table.get(index).1)
+    }
+
+    pub fn get_mut<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<&mut V> {
+        let index = self.find(key)?;
+        Some(&mut self.table.get_mut(index).1)
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.find(key).is_some()
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_hashed(self.hash(&key), key, value)
+    }
+
+    // `insert` for a key whose hash is already known.
+    fn insert_hashed(&mut self, hash: u64, key: K, value: V) -> Option<V> {
+        if let Some(index) = self.find_hashed(hash, &key) {
+            return Some(mem::replace(&mut self.table.get_mut(index).1, value));
+        }
+        self.prepare_insert();
+        self.push_new(hash, key, value);
+        None
+    }
+
+    /// Like `insert`, but fails instead of allocating, handing the pair back.
+    /// Overwriting an existing key always succeeds; a new key needs the
+    /// map to be below its [`capacity`](HashMap::capacity).
+    pub fn insert_within_capacity(&mut self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let hash = self.hash(&key);
+        if let Some(index) = self.find_hashed(hash, &key) {
+            return Ok(Some(mem::replace(&mut self.table.get_mut(index).1, value)));
+        }
+        if self.table.needs_grow() {
+            return Err((key, value));
+        }
+        self.push_new(hash, key, value);
+        Ok(None)
+    }
+
+    /// Returns the value for `key`, calling `f` to create it if the key is
+    /// missing. If `f` fails the error is returned and the map is unchanged.
+    pub fn get_or_try_insert_with<F, E>(&mut self, key: K, f: F) -> Result<&mut V, E>
+        where F: FnOnce() -> Result<V, E>,
+    {
+        let hash = self.hash(&key);
+        let index = match self.find_hashed(hash, &key) {
+            Some(index) => index,
+            None => {
+                let value = f()?;
+                self.prepare_insert();
+                self.push_new(hash, key, value)
+            }
+        };
+        Ok(&mut self.table.get_mut(index).1)
+    }
+
+    /// Reserves a place for `key` before its value exists. The returned
+    /// slot is filled with [`ReservedSlot::fill`] or abandoned with
+    /// [`ReservedSlot::abort`] (or by dropping it); either way the key is
+    /// only hashed here, and any resize happens here rather than on fill.
+    ///
+    /// If `key` is already present it is handed back in `Err`.
+    pub fn reserve_slot(&mut self, key: K) -> Result<ReservedSlot<'_, K, V, S>, K> {
+        let hash = self.hash(&key);
+        if self.find_hashed(hash, &key).is_some() {
+            return Err(key);
+        }
+        self.prepare_insert();
+        Ok(ReservedSlot { map: self, key, hash })
+    }
+
+    /// Moves the entry for `key` into `dest`, returning the value it
+    /// replaced there. The hash stored with the entry goes with it, so
+    /// `dest` never hashes the key.
+    ///
+    /// `dest` must hash exactly as this map does, e.g. by being built with
+    /// a clone of its hasher; with any other hasher the moved entry can't
+    /// be found there. The `verify-hash` feature checks this.
+    pub fn transfer<Q>(&mut self, key: &Q, dest: &mut HashMap<K, V, S>) -> Result<Option<V>, KeyNotFound>
+        where Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.find(key).ok_or(KeyNotFound)?;
+        let (hash, (key, value)) = self.remove_index(index);
+
+        if let Some(index) = dest.find_hashed(hash, &key) {
+            return Ok(Some(mem::replace(&mut dest.table.get_mut(index).1, value)));
+        }
+        dest.prepare_insert();
+        let index = dest.push_new(hash, key, value);
+        // Catches a `dest` that hashes differently, with `verify-hash`.
+        dest.verify_bucket(index);
+        Ok(None)
+    }
 
-        if self.buckets.is_empty() || self.items > 3 * self.buckets.len() /4 {
+    // Grows the table if one more entry would overload it, after moving
+    // an incremental resize along.
+    fn prepare_insert(&mut self) {
+        self.migrate_step();
+        if self.table.needs_grow() {
             self.resize();
         }
+    }
+
+    fn migrate_step(&mut self) {
+        if let Some(step) = self.policy.step {
+            self.migrate(step);
+        }
+    }
 
-        let b_idx = self.bucket_idx(&key);
-        let bucket = &mut self.buckets[b_idx];
+    // Moves the entries of up to `buckets` old buckets into the new table
+    // of an incremental resize, freeing the old one once it is empty. With
+    // `verify-hash` each entry is checked on the way, as in `resize_to`.
+    fn migrate(&mut self, buckets: usize) {
+        let hash_builder = &self.hash_builder;
+        if let Some(old) = self.table.migrate(buckets, |old, slot| verify_slot(hash_builder, old, slot)) {
+            report_alloc(&self.alloc_hook, AllocKind::Dealloc, old.alloc_bytes(), AllocReason::Grow);
+        }
+    }
 
-        for &mut (ref ekey, ref mut evalue) in bucket.iter_mut() {
-            if ekey == &key {
-                return Some(mem::replace(evalue, value));
+    fn push_new(&mut self, hash: u64, key: K, value: V) -> usize {
+        let (index, chain_len) = self.table.insert(hash, (key, value));
+        self.peak_len = self.peak_len.max(self.len());
+
+        if let Some(alarm) = &self.alarm {
+            let report = self.collision_report(chain_len);
+            if report.chain_len > report.bound {
+                alarm(&report);
             }
         }
-        bucket.push((key, value));
-        self.items +=1;
+        index
+    }
+
+    /// Like `insert`, but an existing entry has its key replaced as well as
+    /// its value, and the previous pair is returned.
+    pub fn replace(&mut self, key: K, value: V) -> Option<(K, V)> {
+        let hash = self.hash(&key);
+        if let Some(index) = self.find_hashed(hash, &key) {
+            return Some(mem::replace(self.table.get_mut(index), (key, value)));
+        }
+        self.prepare_insert();
+        self.push_new(hash, key, value);
         None
     }
 
-    fn bucket_idx(&mut self, key: &K) -> usize {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        return (hasher.finish() % self.buckets.len() as u64) as usize;
+    /// Inserts `key` until the returned guard is dropped, at which point the
+    /// entry is removed again, or its previous value restored if `insert`
+    /// replaced one. The map stays usable through the guard.
+    pub fn insert_scoped(&mut self, key: K, value: V) -> ScopedInsert<'_, K, V, S>
+        where K: Clone,
+    {
+        let shadowed = self.insert(key.clone(), value);
+        ScopedInsert { map: self, key: Some(key), shadowed }
     }
 
-    fn resize(&mut self) {
-        let target_size = match self.buckets.len() {
-            0 => INITIAL_NBUCKETS,
-            n => 2 * n,
+    /// Removes and yields up to `n` arbitrary entries, leaving the rest in
+    /// place. Like `Vec::drain`, dropping the iterator early still removes
+    /// the remainder of the `n` entries.
+    pub fn drain_take(&mut self, n: usize) -> DrainTake<'_, K, V, S> {
+        self.migrate_step();
+        DrainTake { map: self, cursor: 0, remaining: n }
+    }
+
+    /// Splits iteration into `m` iterators over disjoint, roughly equal
+    /// ranges of buckets, e.g. to hand one to each of `m` scoped threads.
+    ///
+    /// Panics if `m` is zero.
+    pub fn iter_chunks(&self, m: usize) -> Vec<Iter<'_, K, V>> {
+        assert!(m > 0, "iter_chunks needs at least one chunk");
+        let nbuckets = self.table.positions();
+        (0..m)
+            .map(|i| {
+                let (start, end) = (i * nbuckets / m, (i + 1) * nbuckets / m);
+                let remaining = (start..end).filter(|&index| self.table.is_occupied(index)).count();
+                Iter { inner: self.table.iter_range(start, end), remaining }
+            })
+            .collect()
+    }
+
+    /// Keeps only the entries whose key is in `keys`, which can be any
+    /// [`KeyFilter`]: a [`HashSet`], another map's [`KeySet`], and so on.
+    pub fn retain_keys_in<F: KeyFilter<K> + ?Sized>(&mut self, keys: &F) {
+        self.retain(|key, _| keys.contains_key(key));
+    }
+
+    /// Removes every entry whose key is in `keys`.
+    pub fn remove_keys_in<F: KeyFilter<K> + ?Sized>(&mut self, keys: &F) {
+        if keys.key_count() < self.len() {
+            keys.for_each_key(&mut |key| {
+                self.remove(key);
+            });
+        } else {
+            self.retain(|key, _| !keys.contains_key(key));
+        }
+    }
+
+    /// Incremental `retain`: visits at most `max_work` entries, dropping
+    /// those for which `keep` returns false, and picks up where it left off
+    /// on the next call. Returns `true` once a call completes the pass over
+    /// the whole table; the following call starts a new one.
+    ///
+    /// Entries inserted between calls are visited if they land after the
+    /// cursor, and entries moved past it by those inserts may be visited
+    /// twice. So may an entry that wrapped around to the start of the
+    /// table: dropping the entry in the last bucket shifts it back there.
+    /// Each call moves an incremental resize along once, before it starts,
+    /// and an entry that moves to a bucket behind the cursor is left for
+    /// the next pass. A resize restarts the pass, so every entry that stays
+    /// in the map is eventually visited.
+    ///
+    /// Panics if `max_work` is zero.
+    pub fn prune<F>(&mut self, mut keep: F, max_work: usize) -> bool
+        where F: FnMut(&K, &mut V) -> bool,
+    {
+        assert!(max_work > 0, "prune needs room for at least one entry");
+        self.migrate_step();
+        let mut index = self.prune_cursor;
+        let mut work = 0;
+        while index < self.table.positions() {
+            if !self.table.is_occupied(index) {
+                index += 1;
+                continue;
+            }
+            if work == max_work {
+                self.prune_cursor = index;
+                return false;
+            }
+            work += 1;
+            let (key, value) = self.table.get_mut(index);
+            if keep(key, value) {
+                index += 1;
+            } else {
+                // The rest of the cluster shifts back; the next entry
+                // moves into `index` and is visited next.
+                self.table.remove(index);
+            }
+        }
+        self.prune_cursor = 0;
+        true
+    }
+
+    /// Keeps only the entries for which `f` returns true, in a single pass
+    /// over the table; nothing is rehashed.
+    pub fn retain<F>(&mut self, mut f: F)
+        where F: FnMut(&K, &mut V) -> bool,
+    {
+        // Passes like this one move an incremental resize along before they
+        // start, never during.
+        self.migrate_step();
+        self.table.retain(|(key, value)| f(key, value));
+    }
+
+    /// Whether every key of this map is also a key of `other`, ignoring
+    /// values.
+    pub fn keys_subset_of<W, T: BuildHasher>(&self, other: &HashMap<K, W, T>) -> bool {
+        self.len() <= other.len() && self.keys().all(|key| other.find(key).is_some())
+    }
+
+    /// Whether the two maps have no key in common. Iterates the smaller map
+    /// and probes the larger one.
+    pub fn keys_disjoint_with<W, T: BuildHasher>(&self, other: &HashMap<K, W, T>) -> bool {
+        if self.len() <= other.len() {
+            self.keys().all(|key| other.find(key).is_none())
+        } else {
+            other.keys().all(|key| self.find(key).is_none())
+        }
+    }
+
+    /// Whether both maps hold exactly the same set of keys.
+    pub fn same_keys<W, T: BuildHasher>(&self, other: &HashMap<K, W, T>) -> bool {
+        self.len() == other.len() && self.keys_subset_of(other)
+    }
+
+    /// Swaps the values stored under `a` and `b` without moving the keys.
+    /// Fails, leaving the map untouched, if either key is missing.
+    pub fn swap_values<Q: Hash + Equivalent<K> + ?Sized>(&mut self, a: &Q, b: &Q) -> Result<(), KeyNotFound> {
+        let a = self.find(a).ok_or(KeyNotFound)?;
+        let b = self.find(b).ok_or(KeyNotFound)?;
+        if a != b {
+            let (a, b) = self.table.get_pair_mut(a, b);
+            mem::swap(&mut a.1, &mut b.1);
+        }
+        Ok(())
+    }
+
+    // Bucket holding `key`.
+    fn find<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<usize> {
+        self.find_hashed(self.hash(key), key)
+    }
+
+    fn find_hashed<Q: Equivalent<K> + ?Sized>(&self, hash: u64, key: &Q) -> Option<usize> {
+        for index in self.table.probe(hash) {
+            self.verify_bucket(index);
+        }
+        self.table.find(hash, |(ekey, _)| key.equivalent(ekey))
+    }
+
+    // `verify_slot` for the bucket at `index`, in whichever table holds it.
+    fn verify_bucket(&self, index: usize) {
+        let (table, slot) = self.table.locate(index);
+        verify_slot(&self.hash_builder, table, slot);
+    }
+
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        self.hash_builder.hash_one(key)
+    }
+
+    /// Grows the table once so that `additional` more entries fit
+    /// without any further resize.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        let target_size = self.table.slots_for(self.len() + additional);
+        if target_size > self.table.slots() {
+            self.resize_to(target_size, AllocReason::Grow);
+        }
+    }
+
+    /// Shrinks the table to the smallest size that holds the current
+    /// entries, freeing it entirely if the map is empty.
+    pub fn shrink_to_fit(&mut self) {
+        let target_size = match self.len() {
+            0 => 0,
+            n => self.table.slots_for(n),
         };
+        if target_size < self.table.slots() {
+            self.resize_to(target_size, AllocReason::Shrink);
+        }
+    }
 
-        let mut new_buckets = Vec::with_capacity(target_size);
-        new_buckets.extend((0..target_size).map(|_| Vec::new()));
+    fn resize(&mut self) {
+        let target_size = self.table.grown_slots();
+        if self.policy.step.is_none() {
+            return self.resize_to(target_size, AllocReason::Grow);
+        }
+        // Inserts outpaced the resize under way, so finish it first.
+        self.migrate(usize::MAX);
+        let old_bytes = self.table.alloc_bytes();
+        self.table.begin_resize(target_size);
+        report_alloc(&self.alloc_hook, AllocKind::Alloc, self.table.alloc_bytes() - old_bytes, AllocReason::Grow);
+        self.resizes += 1;
+        self.prune_cursor = 0;
+    }
 
-        for (key, value) in self.buckets.iter_mut().flat_map(|bucket| bucket.drain(..)) {
-            let mut hasher = DefaultHasher::new();
-            key.hash(&mut hasher);
-            let b_idx = (hasher.finish() % new_buckets.len() as u64) as usize;
-            new_buckets[b_idx].push((key, value));
+    fn resize_to(&mut self, target_size: usize, reason: AllocReason) {
+        debug_assert!(target_size == 0 || target_size.is_power_of_two());
+        for index in 0..self.table.positions() {
+            if self.table.is_occupied(index) {
+                self.verify_bucket(index);
+            }
         }
 
-        mem::replace(&mut self.buckets, new_buckets);
+        // Entries move with their stored hashes; no key is hashed again.
+        let old_bytes = self.table.alloc_bytes();
+        self.table.resize(target_size);
+        report_alloc(&self.alloc_hook, AllocKind::Alloc, self.table.alloc_bytes(), reason);
+        report_alloc(&self.alloc_hook, AllocKind::Dealloc, old_bytes, reason);
+        self.resizes += 1;
+        self.prune_cursor = 0;
     }
 }
 
-pub struct Iter<'a, K: 'a, V: 'a>{
-    map: &'a HashMap<K,V>,
-    bucket: usize,
-    at: usize,
+impl<K, V, S> Extend<(K, V)> for HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // Into a non-empty map many of the keys may already be present, so
+        // only reserve for half of them.
+        let (lower, _) = iter.size_hint();
+        let additional = if self.is_empty() { lower } else { lower.div_ceil(2) };
+        self.reserve(additional);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
 }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V>{
-    type Item =  (&'a K, &'a V);
+impl<K, V, S> FromIterator<(K, V)> for HashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = HashMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.map.buckets.get(self.bucket) {
-                Some(bucket) => {
-                    match bucket.get(self.at) {
-                        Some(&(ref k, ref v)) => {
-                            self.at += 1;
-                            break Some((k, v));
-                        }
-                        None => {
-                            self.bucket += 1;
-                            self.at = 0;
-                            continue;
-                        }
-                    }
-                }
-                None => {
-                    break None
-                }
-            }
-        }
+impl<K, V, S> fmt::Debug for HashMap<K, V, S>
+    where K: fmt::Debug,
+          V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
 }
 
-impl<'a, K, V> IntoIterator for &'a HashMap<K,V>{
-    type Item = (&'a K, &'a V);
-    type IntoIter = Iter<'a, K, V>;
+/// The clone has the same entries, hasher and capacity statistics; the
+/// collision alarm and allocation hook are not carried over.
+impl<K, V, S> Clone for HashMap<K, V, S>
+    where K: Clone,
+          V: Clone,
+          S: Clone,
+{
+    fn clone(&self) -> Self {
+        HashMap {
+            table: self.table.clone(),
+            alarm: None,
+            alloc_hook: None,
+            policy: self.policy,
+            peak_len: self.peak_len,
+            resizes: self.resizes,
+            prune_cursor: 0,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
 
-    fn into_iter(self) -> Self::IntoIter {
-        Iter { map: self, bucket: 0, at: 0 }
+/// Maps are equal when they hold the same key-value pairs, whatever order
+/// they iterate in.
+impl<K, V, S> PartialEq for HashMap<K, V, S>
+    where K: Hash + Eq,
+          V: PartialEq,
+          S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<K, V, S> Eq for HashMap<K, V, S>
+    where K: Hash + Eq,
+          V: Eq,
+          S: BuildHasher,
+{
+}
 
-    #[test]
-    fn test_insert() {
-        let mut map = HashMap::new();
-        map.insert("foo", 43);
-        assert_eq!(map.get(&"foo"), Some(&43));
-        assert_eq!(map.remove(&"foo"), Some(43));
-        assert_eq!(map.get(&"foo"), None);
-        assert_eq!(map.remove(&"foo"), None);
+/// Panics if `key` is not in the map.
+impl<K, Q, V, S> Index<&Q> for HashMap<K, V, S>
+    where K: Hash + Eq,
+          Q: Hash + Equivalent<K> + ?Sized,
+          S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in map")
     }
+}
 
-    #[test]
-    fn test_size() {
-        let mut map = HashMap::new();
-        map.insert("foo", 43);
-        assert_eq!(map.len(), 1);
-        assert_eq!(map.is_empty(), false);
-        map.remove(&"foo");
-        assert_eq!(map.len(), 0);
-        assert_eq!(map.is_empty(), true);
+// Hash with fixed keys, for traces that need hashes to agree independently
+// of any map's hasher.
+#[cfg(feature = "record")]
+fn make_hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// With the `verify-hash` feature, checks that the key in `table`'s `slot`
+// still hashes the way it did when it was inserted, panicking with a
+// diagnostic if it doesn't: its `Hash` output has changed, usually through
+// interior mutability, and it can no longer be found reliably. Without the
+// feature this is a no-op.
+#[cfg(feature = "verify-hash")]
+fn verify_slot<K: Hash, V, S: BuildHasher>(hash_builder: &S, table: &RawTable<(K, V)>, slot: usize) {
+    let hash = hash_builder.hash_one(&table.get(slot).0);
+    let nbuckets = table.slots();
+    assert!(
+        table.has_hash(slot, hash),
+        "{} key at bucket {} now hashes to bucket {} of {}; \
+         its Hash changed after it was inserted or is inconsistent with Eq",
+        core::any::type_name::<K>(), slot, hash as usize & (nbuckets - 1), nbuckets,
+    );
+}
+
+#[cfg(not(feature = "verify-hash"))]
+fn verify_slot<K, V, S>(_hash_builder: &S, _table: &RawTable<(K, V)>, _slot: usize) {}
+
+fn report_alloc(hook: &Option<AllocHook>, kind: AllocKind, bytes: usize, reason: AllocReason) {
+    if let Some(hook) = hook {
+        if bytes > 0 {
+            hook(&AllocEvent { kind, bytes, reason });
+        }
     }
+}
 
-    #[test]
-    fn test_iter() {
-        let mut map = HashMap::new();
-        map.insert("foo", 43);
-        map.insert("abc", 44);
-        map.insert("mmm", 45);
+impl<K, V, S> Drop for HashMap<K, V, S> {
+    fn drop(&mut self) {
+        self.table.clear();
+        report_alloc(&self.alloc_hook, AllocKind::Dealloc, self.table.alloc_bytes(), AllocReason::Drop);
+    }
+}
 
-        for (&k, &v) in &map {
-            match k {
-                "foo" => assert_eq!(v, 43),
-                "abc" => assert_eq!(v, 44),
-                "mmm" => assert_eq!(v, 45),
-                _ => unreachable!(),
-            }
+/// Zeroizes every key and value in place, then empties the map.
+///
+/// The map wipes its own storage on remove, resize and drop, but memory owned
+/// by discarded keys and values (a `String`'s buffer, say) is only wiped if
+/// those types do it themselves, e.g. by wrapping them in `zeroize::Zeroizing`.
+#[cfg(feature = "zeroize")]
+impl<K: Zeroize, V: Zeroize, S> Zeroize for HashMap<K, V, S> {
+    fn zeroize(&mut self) {
+        for (key, value) in self.table.iter_mut() {
+            key.zeroize();
+            value.zeroize();
         }
+        self.table.clear();
+    }
+}
 
-        assert_eq!( (&map).into_iter().count(), 3);
+/// Guard returned by [`HashMap::insert_scoped`].
+#[must_use = "the scoped entry is removed as soon as the guard is dropped"]
+pub struct ScopedInsert<'a, K, V, S = DefaultHashBuilder>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    map: &'a mut HashMap<K, V, S>,
+    key: Option<K>,
+    shadowed: Option<V>,
+}
+
+impl<'a, K, V, S> Deref for ScopedInsert<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    type Target = HashMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.map
+    }
+}
+
+impl<'a, K, V, S> DerefMut for ScopedInsert<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.map
     }
-}
\ No newline at end of file
+}
+
+impl<'a, K, V, S> Drop for ScopedInsert<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn drop(&mut self) {
+        let key = self.key.take().unwrap();
+        match self.shadowed.take() {
+            Some(value) => {
+                self.map.insert(key, value);
+            }
+            None => {
+                self.map.remove(&key);
+            }
+        }
+    }
+}
+
+/// Vacant slot returned by [`HashMap::reserve_slot`].
+#[must_use = "the slot is released without inserting anything if it is dropped"]
+pub struct ReservedSlot<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    key: K,
+    hash: u64,
+}
+
+impl<'a, K, V, S> ReservedSlot<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Inserts the entry and returns its value.
+    pub fn fill(self, value: V) -> &'a mut V {
+        let ReservedSlot { map, key, hash } = self;
+        let index = map.push_new(hash, key, value);
+        &mut map.table.get_mut(index).1
+    }
+
+    /// Releases the slot, leaving the map without the key, and returns it.
+    pub fn abort(self) -> K {
+        self.key
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    // Removes an entry from the first occupied bucket at or after
+    // `*cursor`, leaving `*cursor` there for the next call.
+    fn pop_entry(&mut self, cursor: &mut usize) -> Option<(K, V)> {
+        self.table.pop_from(cursor).map(|(_, entry)| entry)
+    }
+}
+
+/// Iterator returned by [`HashMap::drain_take`].
+pub struct DrainTake<'a, K, V, S = DefaultHashBuilder> {
+    map: &'a mut HashMap<K, V, S>,
+    cursor: usize,
+    remaining: usize,
+}
+
+impl<'a, K, V, S> Iterator for DrainTake<'a, K, V, S> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let entry = self.map.pop_entry(&mut self.cursor)?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.remaining.min(self.map.table.len());
+        (len, Some(len))
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for DrainTake<'a, K, V, S> {}
+
+impl<'a, K, V, S> Drop for DrainTake<'a, K, V, S> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+pub struct Iter<'a, K: 'a, V: 'a>{
+    inner: raw::RawIter<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V>{
+    type Item =  (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (k, v) = self.inner.next()?;
+        self.remaining -= 1;
+        Some((k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S>{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::Hasher;
+
+    #[test]
+    fn test_insert() {
+        let mut map = HashMap::new();
+        map.insert("foo", 43);
+        assert_eq!(map.get(&"foo"), Some(&43));
+        assert_eq!(map.remove(&"foo"), Some(43));
+        assert_eq!(map.get(&"foo"), None);
+        assert_eq!(map.remove(&"foo"), None);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut map = HashMap::new();
+        map.insert("foo", 43);
+        assert_eq!(map.len(), 1);
+        assert!(!map.is_empty());
+        map.remove(&"foo");
+        assert_eq!(map.len(), 0);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut map = HashMap::new();
+        map.insert("foo", 43);
+        map.insert("abc", 44);
+        map.insert("mmm", 45);
+
+        for (&k, &v) in &map {
+            match k {
+                "foo" => assert_eq!(v, 43),
+                "abc" => assert_eq!(v, 44),
+                "mmm" => assert_eq!(v, 45),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!( (&map).into_iter().count(), 3);
+    }
+
+    fn index_idents<'arena>(idents: &[&'arena str]) -> HashMap<&'arena str, usize> {
+        let mut map = HashMap::new();
+        for (i, &ident) in idents.iter().enumerate() {
+            map.insert(ident, i);
+        }
+        map
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let arena = String::from("foo bar baz foo");
+        let idents: Vec<&str> = arena.split(' ').collect();
+
+        let mut map = index_idents(&idents);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&"foo"), Some(&3));
+        assert_eq!(map.get(&&arena[4..7]), Some(&1));
+        assert_eq!(map.remove(&"baz"), Some(2));
+        assert_eq!(map.get(&"baz"), None);
+
+        *map.entry(&arena[8..11]).or_insert(0) += 10;
+        *map.entry("foo").or_insert(0) += 10;
+        assert_eq!(map.get(&"baz"), Some(&10));
+        assert_eq!(map.get(&"foo"), Some(&13));
+
+        for (key, value) in map.iter_mut() {
+            *value += key.len();
+        }
+        assert_eq!(map.get(&"bar"), Some(&4));
+
+        let mut drained: Vec<(&str, usize)> = map.drain().collect();
+        drained.sort();
+        assert_eq!(drained, [("bar", 4), ("baz", 13), ("foo", 16)]);
+        assert!(map.is_empty());
+
+        let map = index_idents(&idents);
+        let mut keys: Vec<&str> = map.into_iter().map(|(key, _)| key).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["bar", "baz", "foo"]);
+        assert!(keys.iter().all(|key| arena.contains(key)));
+    }
+
+    #[test]
+    fn test_borrowed_keys_outlive_map() {
+        let arena = String::from("alpha beta gamma");
+        let keys: Vec<&str> = {
+            let map = index_idents(&arena.split(' ').collect::<Vec<_>>());
+            (&map).into_iter().map(|(&k, _)| k).collect()
+        };
+
+        assert_eq!(keys.len(), 3);
+        for key in keys {
+            assert!(arena.contains(key));
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_map() {
+        let mut map = HashMap::new();
+        map.insert(String::from("user"), String::from("hunter2"));
+        map.zeroize();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&String::from("user")), None);
+    }
+
+    #[derive(PartialEq, Eq)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0.hash(state);
+        }
+    }
+
+    // Counts the keys it hashes.
+    #[derive(Clone, Default)]
+    pub(crate) struct CountingState(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl CountingState {
+        pub(crate) fn count(&self) -> usize {
+            self.0.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl BuildHasher for CountingState {
+        type Hasher = PortableHasher;
+
+        fn build_hasher(&self) -> PortableHasher {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            PortableState.build_hasher()
+        }
+    }
+
+    // Hashes a `u64` key to itself, to put entries in chosen buckets.
+    #[derive(Clone, Default)]
+    struct IdentityState;
+
+    struct IdentityHasher(u64);
+
+    impl Hasher for IdentityHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, _: &[u8]) {
+            unreachable!("only u64 keys")
+        }
+
+        fn write_u64(&mut self, n: u64) {
+            self.0 = n;
+        }
+    }
+
+    impl BuildHasher for IdentityState {
+        type Hasher = IdentityHasher;
+
+        fn build_hasher(&self) -> IdentityHasher {
+            IdentityHasher(0)
+        }
+    }
+
+    // A key whose hash can be changed behind the map's back.
+    #[cfg(feature = "verify-hash")]
+    #[derive(PartialEq, Eq)]
+    struct Shifty(std::cell::Cell<u32>);
+
+    #[cfg(feature = "verify-hash")]
+    impl Hash for Shifty {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.get().hash(state);
+        }
+    }
+
+    // A map of eight `Shifty` keys in a table of 16 slots, with the old
+    // table of 8 still holding all but the last.
+    #[cfg(feature = "verify-hash")]
+    fn shifty_migrating() -> HashMap<Shifty, u32> {
+        let mut map = HashMap::new();
+        map.set_resize_policy(ResizePolicy::new().incremental(1));
+        for i in 0..8 {
+            map.insert(Shifty(std::cell::Cell::new(i)), i);
+        }
+        assert_eq!((map.table.slots(), map.table.positions()), (16, 16 + 8));
+        map
+    }
+
+    #[cfg(feature = "verify-hash")]
+    #[test]
+    #[should_panic(expected = "now hashes to bucket")]
+    fn test_verify_hash_catches_mutated_key() {
+        let mut map = HashMap::new();
+        for i in 0..8 {
+            map.insert(Shifty(std::cell::Cell::new(i)), i);
+        }
+        // Change the key's hash behind the map's back.
+        let (key, _) = map.iter().find(|(key, _)| key.0.get() == 3).unwrap();
+        key.0.set(1000);
+        map.get(&Shifty(std::cell::Cell::new(3)));
+    }
+
+    // The report names the old table the key sits in, not the new one.
+    #[cfg(feature = "verify-hash")]
+    #[test]
+    #[should_panic(expected = "of 8; its Hash changed")]
+    fn test_verify_hash_in_old_table() {
+        let map = shifty_migrating();
+        let (key, _) = map.iter().find(|(key, _)| key.0.get() == 3).unwrap();
+        key.0.set(1000);
+        map.get(&Shifty(std::cell::Cell::new(3)));
+    }
+
+    #[cfg(feature = "verify-hash")]
+    #[test]
+    #[should_panic(expected = "of 8; its Hash changed")]
+    fn test_verify_hash_during_migration() {
+        let mut map = shifty_migrating();
+        let (key, _) = map.iter().find(|(key, _)| key.0.get() == 3).unwrap();
+        key.0.set(1000);
+        // Finishes the migration without looking anything up.
+        map.set_resize_policy(ResizePolicy::new());
+    }
+
+    #[test]
+    fn test_collision_alarm() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let raised = Arc::new(AtomicUsize::new(0));
+        let mut map = HashMap::new();
+        let counter = Arc::clone(&raised);
+        map.set_collision_alarm(move |report| {
+            assert!(report.chain_len > report.bound);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for i in 0..8 {
+            map.insert(Colliding(i), i);
+        }
+        assert_eq!(raised.load(Ordering::SeqCst), 0);
+        assert!(map.check_collisions().is_ok());
+
+        for i in 8..64 {
+            map.insert(Colliding(i), i);
+        }
+        assert!(raised.load(Ordering::SeqCst) > 0);
+        assert_eq!(map.check_collisions().unwrap_err().chain_len, 64);
+    }
+
+    #[test]
+    fn test_no_collision_alarm_for_distinct_keys() {
+        let mut map = HashMap::new();
+        map.set_collision_alarm(|report| panic!("unexpected alarm: {:?}", report));
+        for i in 0..10_000 {
+            map.insert(i, i);
+        }
+        assert!(map.check_collisions().is_ok());
+    }
+
+    #[test]
+    fn test_swap_values() {
+        let mut map = HashMap::new();
+        for i in 0..32 {
+            map.insert(i, i * 10);
+        }
+        map.swap_values(&3, &17).unwrap();
+        assert_eq!(map.get(&3), Some(&170));
+        assert_eq!(map.get(&17), Some(&30));
+
+        map.swap_values(&5, &5).unwrap();
+        assert_eq!(map.get(&5), Some(&50));
+
+        assert_eq!(map.swap_values(&3, &99), Err(KeyNotFound));
+        assert_eq!(map.get(&3), Some(&170));
+        assert_eq!(HashMap::<i32, i32>::new().swap_values(&1, &2), Err(KeyNotFound));
+    }
+
+    #[test]
+    fn test_swap_values_same_bucket() {
+        let mut map = HashMap::new();
+        for i in 0..8 {
+            map.insert(Colliding(i), i);
+        }
+        map.swap_values(&Colliding(1), &Colliding(6)).unwrap();
+        assert_eq!(map.get(&Colliding(1)), Some(&6));
+        assert_eq!(map.get(&Colliding(6)), Some(&1));
+    }
+
+    #[derive(Debug)]
+    struct Spanned(&'static str, usize);
+
+    impl PartialEq for Spanned {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Spanned {}
+
+    impl Hash for Spanned {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut map = HashMap::new();
+        assert!(map.replace(Spanned("x", 1), 10).is_none());
+
+        let (old_key, old_value) = map.replace(Spanned("x", 7), 20).unwrap();
+        assert_eq!((old_key.1, old_value), (1, 10));
+        assert_eq!(map.len(), 1);
+
+        let (&Spanned(_, span), &value) = (&map).into_iter().next().unwrap();
+        assert_eq!((span, value), (7, 20));
+
+        // One hash per call, hit or miss.
+        let hasher = CountingState::default();
+        let mut map = HashMap::with_hasher(hasher.clone());
+        map.replace(1, 1);
+        map.replace(1, 2);
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == 2);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with() {
+        let mut map = HashMap::new();
+        let v = map.get_or_try_insert_with("a", || Ok::<_, ()>(1)).unwrap();
+        *v += 1;
+        assert_eq!(map.get_or_try_insert_with("a", || Err("not called")), Ok(&mut 2));
+
+        assert_eq!(map.get_or_try_insert_with("b", || Err::<i32, _>("io error")), Err("io error"));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"b"), None);
+
+        // The key is hashed once, hit or miss, unless `verify-hash` rehashes
+        // the keys it probes.
+        let hasher = CountingState::default();
+        let mut map = HashMap::with_hasher(hasher.clone());
+        map.get_or_try_insert_with(1, || Ok::<_, ()>(1)).unwrap();
+        map.get_or_try_insert_with(1, || Ok::<_, ()>(2)).unwrap();
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == 2);
+    }
+
+    #[test]
+    fn test_reserve_slot() {
+        let mut map = HashMap::new();
+        map.insert("a", 1);
+        assert_eq!(map.reserve_slot("a").err(), Some("a"));
+
+        let slot = map.reserve_slot("b").unwrap();
+        assert_eq!(slot.key(), &"b");
+        *slot.fill(2) += 1;
+        assert_eq!(map.get(&"b"), Some(&3));
+
+        assert_eq!(map.reserve_slot("c").unwrap().abort(), "c");
+        assert_eq!(map.get(&"c"), None);
+        assert_eq!(map.len(), 2);
+
+        // Reserving and filling hashes the key exactly once.
+        let hasher = CountingState::default();
+        let mut map = HashMap::with_hasher(hasher.clone());
+        map.reserve_slot(1).unwrap().fill(1);
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == 1);
+        map.reserve_slot(2).unwrap().fill(2);
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == 2);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut cold: HashMap<_, _> = (0..50).map(|i| (i, i * 10)).collect();
+        let mut hot = HashMap::with_hasher(cold.hasher().clone());
+        hot.insert(7, 0);
+
+        assert_eq!(cold.transfer(&3, &mut hot), Ok(None));
+        assert_eq!(cold.transfer(&7, &mut hot), Ok(Some(0)));
+        assert_eq!(cold.transfer(&7, &mut hot), Err(KeyNotFound));
+
+        assert_eq!(cold.len(), 48);
+        assert_eq!(hot.len(), 2);
+        assert_eq!(hot.get(&3), Some(&30));
+        assert_eq!(hot.get(&7), Some(&70));
+
+        // Only the source lookup hashes the key, whether or not it was in
+        // the destination already.
+        let hasher = CountingState::default();
+        let mut cold = HashMap::with_hasher(hasher.clone());
+        let mut hot = HashMap::with_hasher(hasher.clone());
+        cold.extend((0..50).map(|i| (i, i)));
+        hot.insert(7, 0);
+        let before = hasher.count();
+        cold.transfer(&3, &mut hot).unwrap();
+        cold.transfer(&7, &mut hot).unwrap();
+        assert!(cfg!(feature = "verify-hash") || hasher.count() == before + 2);
+        assert_eq!((hot.get(&3), hot.get(&7)), (Some(&3), Some(&7)));
+    }
+
+    #[test]
+    fn test_borrowed_lookups() {
+        let mut map = HashMap::new();
+        map.insert(String::from("a"), 1);
+        map.insert(String::from("b"), 2);
+
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.swap_values("a", "b"), Ok(()));
+        assert_eq!(map.remove("b"), Some(1));
+        assert!(map.key_set().contains("a"));
+        assert_eq!(map.get("b"), None);
+    }
+
+    #[test]
+    fn test_insert_within_capacity() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let allocs = Arc::new(AtomicUsize::new(0));
+        let mut map = HashMap::new();
+        assert_eq!(map.insert_within_capacity("a", 1), Err(("a", 1)));
+
+        for key in ["a", "b", "c", "d"].iter().copied() {
+            map.insert(key, 0);
+        }
+        map.remove(&"c");
+        let counter = Arc::clone(&allocs);
+        map.set_alloc_hook(move |event| {
+            if event.kind == AllocKind::Alloc {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        assert_eq!(map.insert_within_capacity("a", 1), Ok(Some(0)));
+        assert_eq!(map.insert_within_capacity("c", 3), Ok(None));
+        assert_eq!(map.get(&"c"), Some(&3));
+        assert_eq!(allocs.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        let mut a = HashMap::with_hasher(PortableState);
+        let mut b = HashMap::with_hasher(*a.hasher());
+        for i in 0..100 {
+            a.insert(i, i);
+            b.insert(i, i * 2);
+        }
+        // Same hasher, same layout: both iterate keys in the same order.
+        let order = |map: &HashMap<i32, i32, PortableState>| map.into_iter().map(|(&k, _)| k).collect::<Vec<_>>();
+        assert_eq!(order(&a), order(&b));
+        assert_eq!(a.get(&42), Some(&42));
+        assert_eq!(b.remove(&42), Some(84));
+
+        let collected: HashMap<_, _, PortableState> = (0..10).map(|i| (i, ())).collect();
+        assert_eq!(collected.len(), 10);
+    }
+
+    #[test]
+    fn test_shared_lookups() {
+        let mut map = HashMap::new();
+        map.insert(String::from("one"), 1);
+        let shared = &map;
+        assert_eq!(shared.get("one"), Some(&1));
+        assert!(shared.contains_key("one"));
+        assert!(!shared.contains_key("two"));
+
+        *map.get_mut("one").unwrap() += 10;
+        assert_eq!(map.get("one"), Some(&11));
+        assert_eq!(map.get_mut("two"), None);
+        assert!(!HashMap::<String, ()>::new().contains_key("one"));
+    }
+
+    #[test]
+    fn test_alloc_hook() {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut map = HashMap::new();
+        let log = Arc::clone(&events);
+        map.set_alloc_hook(move |event| log.lock().unwrap().push(*event));
+
+        for i in 0..100u64 {
+            map.insert(i, i);
+        }
+        let net = |events: &[AllocEvent]| {
+            events.iter().fold(0isize, |net, e| match e.kind {
+                AllocKind::Alloc => net + e.bytes as isize,
+                AllocKind::Dealloc => net - e.bytes as isize,
+            })
+        };
+        let live = map.table.slots() * (mem::size_of::<u64>() + mem::size_of::<(u64, u64)>());
+        {
+            let events = events.lock().unwrap();
+            assert_eq!(net(&events), live as isize);
+            assert!(events.iter().all(|e| e.reason == AllocReason::Grow));
+        }
+
+        drop(map);
+        let events = events.lock().unwrap();
+        assert_eq!(net(&events), 0);
+        assert_eq!(events.last().unwrap().reason, AllocReason::Drop);
+    }
+
+    #[test]
+    fn test_insert_scoped() {
+        let mut map = HashMap::new();
+        map.insert("level", "info");
+        {
+            let mut guard = map.insert_scoped("level", "debug");
+            assert_eq!(guard.get(&"level"), Some(&"debug"));
+            {
+                let inner = guard.insert_scoped("trace", "on");
+                assert_eq!(inner.get(&"trace"), Some(&"on"));
+                assert_eq!(inner.len(), 2);
+            }
+            assert_eq!(guard.get(&"trace"), None);
+        }
+        assert_eq!(map.get(&"level"), Some(&"info"));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_drain_take() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.insert(i, i * i);
+        }
+
+        let batch: Vec<_> = map.drain_take(4).collect();
+        assert_eq!(batch.len(), 4);
+        assert_eq!(map.len(), 6);
+        for (k, v) in &batch {
+            assert_eq!(*v, k * k);
+            assert_eq!(map.get(k), None);
+        }
+
+        let mut drain = map.drain_take(3);
+        assert_eq!(drain.len(), 3);
+        drain.next();
+        drop(drain);
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.drain_take(100).count(), 3);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let mut map = HashMap::new();
+        for i in 0..1000u64 {
+            map.insert(i, i);
+        }
+
+        let chunks = map.iter_chunks(4);
+        assert_eq!(chunks.len(), 4);
+        let sums: Vec<u64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = chunks
+                .into_iter()
+                .map(|chunk| scope.spawn(move || chunk.map(|(_, &v)| v).sum::<u64>()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(sums.iter().sum::<u64>(), (0..1000).sum::<u64>());
+
+        let counts: Vec<usize> = map.iter_chunks(7).into_iter().map(Iterator::count).collect();
+        assert_eq!(counts.iter().sum::<usize>(), 1000);
+        assert_eq!(HashMap::<u8, u8>::new().iter_chunks(3).into_iter().flatten().count(), 0);
+    }
+
+    #[test]
+    fn test_retain_and_remove_keys_in() {
+        let mut map = HashMap::new();
+        for i in 0..20 {
+            map.insert(i, i);
+        }
+        let evens: std::collections::HashSet<i32> = (0..40).step_by(2).collect();
+        map.retain_keys_in(&evens);
+        assert_eq!(map.len(), 10);
+        assert!((&map).into_iter().all(|(k, _)| k % 2 == 0));
+
+        let few: std::collections::HashSet<i32> = [0, 2, 3].iter().copied().collect();
+        map.remove_keys_in(&few);
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.get(&0), None);
+
+        map.remove_keys_in(&evens);
+        assert!(map.is_empty());
+
+        // Any key filter will do.
+        map.extend((0..20).map(|i| (i, i)));
+        map.retain_keys_in(&(0..15).collect::<HashSet<i32>>());
+        map.remove_keys_in(&[1, 2, 3].iter().copied().collect::<std::collections::BTreeSet<i32>>());
+        let other: HashMap<i32, ()> = (10..30).map(|i| (i, ())).collect();
+        map.remove_keys_in(&other.key_set());
+        let mut keys: Vec<i32> = map.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [0, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn test_key_set_relations() {
+        let mut small = HashMap::new();
+        let mut large = HashMap::new();
+        for i in 0..5 {
+            small.insert(i, "small");
+        }
+        for i in 0..50 {
+            large.insert(i, i as f64);
+        }
+        assert!(small.keys_subset_of(&large));
+        assert!(!large.keys_subset_of(&small));
+        assert!(!small.same_keys(&large));
+        assert!(!small.keys_disjoint_with(&large));
+
+        let mut other = HashMap::new();
+        for i in 100..103 {
+            other.insert(i, ());
+        }
+        assert!(other.keys_disjoint_with(&large));
+        assert!(large.keys_disjoint_with(&other));
+
+        let mut same = HashMap::new();
+        for i in (0..5).rev() {
+            same.insert(i, i);
+        }
+        assert!(small.same_keys(&same));
+        assert!(HashMap::<i32, ()>::new().keys_subset_of(&small));
+    }
+
+    #[test]
+    fn test_capacity_report() {
+        let mut map = HashMap::new();
+        assert_eq!(map.capacity_report().resizes, 0);
+        for i in 0..100 {
+            map.insert(i, ());
+        }
+        for i in 0..60 {
+            map.remove(&i);
+        }
+        map.insert(1000, ());
+
+        let report = map.capacity_report();
+        assert_eq!(report.peak_len, 100);
+        assert_eq!(report.suggested_capacity, 100);
+        assert_eq!(report.resizes, 9);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut map = HashMap::with_capacity(100);
+        assert!(map.capacity() >= 100);
+        assert!(map.table.slots().is_power_of_two());
+        assert_eq!(map.capacity_report().resizes, 1);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.capacity_report().resizes, 1);
+
+        map.reserve(1000);
+        assert!(map.capacity() >= 1100);
+        for i in 0..90 {
+            map.remove(&i);
+        }
+        map.shrink_to_fit();
+        assert!(map.capacity() >= 10);
+        assert!(map.table.slots() <= 16);
+        assert_eq!(map.get(&95), Some(&95));
+
+        map.drain();
+        map.shrink_to_fit();
+        assert_eq!(map.capacity(), 0);
+        assert_eq!(HashMap::<u8, u8>::with_capacity(0).capacity(), 0);
+    }
+
+    #[test]
+    fn test_load_factor() {
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        map.reserve(90);
+        assert_eq!(map.table.slots(), 128);
+
+        let mut sparse: HashMap<u32, u32> = HashMap::new();
+        sparse.set_resize_policy(ResizePolicy::new().max_load_factor(0.5));
+        sparse.reserve(90);
+        assert_eq!(sparse.table.slots(), 256);
+        assert_eq!(sparse.capacity(), 129);
+        sparse.extend((0..500).map(|i| (i, i)));
+        assert!(sparse.len() * 2 <= sparse.table.slots());
+
+        // Lowering it below the current fill grows on the next insert.
+        map.extend((0..90).map(|i| (i, i)));
+        map.set_resize_policy(ResizePolicy::new().max_load_factor(0.25));
+        map.insert(1000, 0);
+        assert_eq!(map.table.slots(), 256);
+        assert_eq!(map.get(&89), Some(&89));
+    }
+
+    #[test]
+    fn test_incremental_resize() {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let mut map = HashMap::new();
+        map.set_alloc_hook(move |event: &AllocEvent| log.lock().unwrap().push(*event));
+        map.set_resize_policy(ResizePolicy::new().incremental(3));
+        map.extend((0..97u64).map(|i| (i, i)));
+        assert_eq!(map.table.slots(), 128);
+        assert_eq!(map.table.positions(), 128);
+
+        // The next insert allocates the new table but moves almost nothing.
+        map.insert(97, 97);
+        assert_eq!(map.table.slots(), 256);
+        assert_eq!(map.table.positions(), 256 + 128);
+        assert_eq!(map.len(), 98);
+        assert!((0..98).all(|i| map.get(&i) == Some(&i)));
+        assert_eq!(map.iter().count(), 98);
+        assert_eq!(map.remove(&40), Some(40));
+        map.swap_values(&0, &97).unwrap();
+        assert_eq!((map[&0], map[&97]), (97, 0));
+        map.retain(|&k, _| k != 50);
+        assert_eq!(map.clone().into_iter().count(), 96);
+
+        // Each insert takes three steps through the 128 old buckets and
+        // the 95 entries left in them, finishing before the next resize.
+        let mut calls = 0;
+        while map.table.positions() > map.table.slots() {
+            map.insert(1000 + calls, 0);
+            calls += 1;
+        }
+        assert!((70..=75).contains(&calls));
+        assert_eq!(map.table.slots(), 256);
+        assert_eq!(map.len(), 96 + calls as usize);
+        assert!((1..97).filter(|&i| i != 40 && i != 50).all(|i| map.get(&i) == Some(&i)));
+        let net = events.lock().unwrap().iter().fold(0isize, |net, e| match e.kind {
+            AllocKind::Alloc => net + e.bytes as isize,
+            AllocKind::Dealloc => net - e.bytes as isize,
+        });
+        assert_eq!(net, map.table.alloc_bytes() as isize);
+
+        // Turning it off finishes a resize under way.
+        for i in 2000..2100 {
+            map.insert(i, 0);
+        }
+        assert!(map.table.positions() > map.table.slots());
+        map.set_resize_policy(ResizePolicy::new());
+        assert_eq!(map.table.positions(), map.table.slots());
+        assert_eq!(map.get(&2099), Some(&0));
+    }
+
+    #[test]
+    fn test_collect_allocates_once() {
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get(&999), Some(&999));
+        assert_eq!(map.capacity_report().resizes, 1);
+
+        map.extend((1000..1010).map(|i| (i, i)));
+        assert_eq!(map.len(), 1010);
+        assert_eq!(map.capacity_report().resizes, 1);
+
+        for n in 0..200 {
+            let map: HashMap<usize, ()> = (0..n).map(|i| (i, ())).collect();
+            assert!(map.capacity_report().resizes <= 1, "{} entries", n);
+        }
+    }
+
+    #[test]
+    fn test_std_traits() {
+        let mut map: HashMap<String, Vec<u8>> = HashMap::default();
+        map.extend((0..10u8).map(|i| (i.to_string(), vec![i])));
+        let copy = map.clone();
+        assert_eq!(copy, map);
+        assert_eq!(copy["7"], [7]);
+
+        map.insert(String::from("7"), Vec::new());
+        assert_ne!(copy, map);
+        let mut pairs: Vec<_> = copy.clone().into_iter().collect();
+        pairs.reverse();
+        let reordered: HashMap<String, Vec<u8>> = pairs.into_iter().collect();
+        assert_eq!(reordered, copy);
+
+        let single: HashMap<u8, &str> = std::iter::once((1, "one")).collect();
+        assert_eq!(format!("{:?}", single), r#"{1: "one"}"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found")]
+    fn test_index_missing_key() {
+        let map: HashMap<&str, u8> = HashMap::new();
+        let _ = map["absent"];
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut calls = 0;
+        while !map.prune(|_, v| *v % 3 == 0, 50) {
+            calls += 1;
+            assert!(calls < 100);
+        }
+        assert!(calls >= 1000 / 50 - 1);
+        assert_eq!(map.len(), 334);
+        assert!((&map).into_iter().all(|(_, v)| v % 3 == 0));
+    }
+
+    #[test]
+    fn test_prune_across_mutation() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        assert!(!map.prune(|_, _| false, 10));
+        assert_eq!(map.len(), 90);
+
+        for i in 100..400 {
+            map.insert(i, i);
+        }
+        while !map.prune(|_, v| *v >= 300, 10) {}
+        let mut keys: Vec<u32> = (&map).into_iter().map(|(&k, _)| k).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (300..400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one entry")]
+    fn test_prune_no_work() {
+        let mut map: HashMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        map.prune(|_, _| true, 0);
+    }
+
+    #[test]
+    fn test_prune_wrapped_entry_visited_twice() {
+        let mut map = HashMap::with_capacity_and_hasher(7, IdentityState);
+        map.insert(1u64, ());
+        map.insert(7, ());
+        // Both want the last bucket; 15 wraps around to the first.
+        map.insert(15, ());
+        assert_eq!(map.table.slots(), 8);
+
+        let mut visits = Vec::new();
+        assert!(map.prune(|&k, _| {
+            visits.push(k);
+            k != 7
+        }, 10));
+        assert_eq!(visits, [15, 1, 7, 15]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_prune_during_migration() {
+        let mut map = HashMap::with_hasher(IdentityState);
+        map.set_resize_policy(ResizePolicy::new().incremental(1));
+        for k in [1u64, 2, 3, 4, 5, 6, 7, 9, 10] {
+            map.insert(k, ());
+        }
+        assert_eq!((map.table.slots(), map.table.positions()), (16, 16 + 8));
+
+        // 2 moves into the new table once the cursor is past its bucket.
+        let mut visits = Vec::new();
+        while !map.prune(|&k, _| {
+            visits.push(k);
+            true
+        }, 1) {}
+        assert_eq!(visits, [1, 9, 10, 3, 4, 5, 6, 7]);
+
+        // The next pass visits it.
+        visits.clear();
+        assert!(map.prune(|&k, _| {
+            visits.push(k);
+            true
+        }, 100));
+        visits.sort_unstable();
+        assert_eq!(visits, [1, 2, 3, 4, 5, 6, 7, 9, 10]);
+    }
+}
@@ -0,0 +1,598 @@
+use alloc::vec::{self, Vec};
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::mem;
+use core::slice;
+
+use crate::raw::RawTable;
+use crate::{DefaultHashBuilder, Equivalent};
+#[cfg(feature = "std")]
+use crate::SeededState;
+
+/// Map that remembers insertion order, with positional access.
+///
+/// Entries live in a dense `Vec` in the order they were inserted, and the
+/// hash table only maps each key to its position there, so iteration is a
+/// plain slice walk. Overwriting a key keeps its position. The table is the
+/// same Robin Hood table [`HashMap`](crate::HashMap) uses, growing by the
+/// same rule.
+///
+/// ```
+/// use hashmap::OrderedHashMap;
+///
+/// let mut map = OrderedHashMap::new();
+/// map.insert("b", 2);
+/// map.insert("a", 1);
+/// map.insert("c", 3);
+/// assert_eq!(map.keys().copied().collect::<String>(), "bac");
+///
+/// assert_eq!(map.shift_remove(&"b"), Some(2));
+/// assert_eq!(map.get_index(0), Some((&"a", &1)));
+/// ```
+pub struct OrderedHashMap<K, V, S = DefaultHashBuilder> {
+    indices: RawTable<usize>,
+    entries: Vec<Bucket<K, V>>,
+    hash_builder: S,
+}
+
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V, S> Default for OrderedHashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> OrderedHashMap<K, V, SeededState>
+    where K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self::with_hasher(SeededState::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, SeededState::new())
+    }
+}
+
+impl<K, V, S> OrderedHashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn with_hasher(hash_builder: S) -> Self {
+        OrderedHashMap { indices: RawTable::new(), entries: Vec::new(), hash_builder }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let mut map = Self::with_hasher(hash_builder);
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How many entries the map holds before the next insert resizes its
+    /// table.
+    pub fn capacity(&self) -> usize {
+        self.indices.capacity()
+    }
+
+    /// Grows the table and the entry storage once so that `additional`
+    /// more entries fit without reallocating.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional == 0 {
+            return;
+        }
+        let target_size = self.indices.slots_for(self.len() + additional);
+        if target_size > self.indices.slots() {
+            self.indices.resize(target_size);
+        }
+        self.entries.reserve(additional);
+    }
+
+    /// Inserts at the end, or overwrites the value in place if `key` is
+    /// already present, returning the old value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_full(key, value).1
+    }
+
+    /// Like `insert`, but also returns the entry's position.
+    pub fn insert_full(&mut self, key: K, value: V) -> (usize, Option<V>) {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some((_, index)) = self.find_hashed(hash, &key) {
+            return (index, Some(mem::replace(&mut self.entries[index].value, value)));
+        }
+        if self.indices.needs_grow() {
+            self.indices.resize(self.indices.grown_slots());
+        }
+        let index = self.entries.len();
+        self.indices.insert(hash, index);
+        self.entries.push(Bucket { hash, key, value });
+        (index, None)
+    }
+
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        let (_, index) = self.find(key)?;
+        Some(&self.entries[index].value)
+    }
+
+    pub fn get_mut<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<&mut V> {
+        let (_, index) = self.find(key)?;
+        Some(&mut self.entries[index].value)
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.find(key).is_some()
+    }
+
+    /// Position of `key` in insertion order.
+    pub fn get_index_of<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<usize> {
+        self.find(key).map(|(_, index)| index)
+    }
+
+    /// Removes `key` by moving the last entry into its place: O(1), but the
+    /// order of the remaining entries changes.
+    pub fn swap_remove<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let (_, index) = self.find(key)?;
+        self.swap_remove_index(index).map(|(_, value)| value)
+    }
+
+    /// Removes `key` and shifts the following entries down by one,
+    /// preserving order. This is O(n); prefer `swap_remove` when order
+    /// doesn't matter.
+    pub fn shift_remove<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let (slot, index) = self.find(key)?;
+        self.indices.remove(slot);
+        let removed = self.entries.remove(index);
+        for i in self.indices.iter_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        Some(removed.value)
+    }
+
+    // Slot in the index table and position in `entries` of `key`.
+    fn find<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<(usize, usize)> {
+        self.find_hashed(self.hash_builder.hash_one(key), key)
+    }
+
+    fn find_hashed<Q: Equivalent<K> + ?Sized>(&self, hash: u64, key: &Q) -> Option<(usize, usize)> {
+        let entries = &self.entries;
+        let slot = self.indices.find(hash, |&i| key.equivalent(&entries[i].key))?;
+        Some((slot, *self.indices.get(slot)))
+    }
+}
+
+impl<K, V, S> OrderedHashMap<K, V, S> {
+    /// The entry at `index` in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|bucket| (&bucket.key, &bucket.value))
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries.get_mut(index).map(|bucket| (&bucket.key, &mut bucket.value))
+    }
+
+    /// Removes the entry at `index` by moving the last entry into its
+    /// place, like `swap_remove`.
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        let slot = self.slot_of(index);
+        self.indices.remove(slot);
+        let removed = self.entries.swap_remove(index);
+        if index < self.entries.len() {
+            // The former last entry now lives at `index`.
+            let slot = self.slot_of_moved(index, self.entries.len());
+            *self.indices.get_mut(slot) = index;
+        }
+        Some((removed.key, removed.value))
+    }
+
+    /// Swaps the positions of the entries at `a` and `b`.
+    ///
+    /// Panics if either is out of bounds.
+    pub fn swap_indices(&mut self, a: usize, b: usize) {
+        assert!(a < self.entries.len() && b < self.entries.len(), "index out of bounds: the len is {}", self.entries.len());
+        if a == b {
+            return;
+        }
+        let (slot_a, slot_b) = (self.slot_of(a), self.slot_of(b));
+        *self.indices.get_mut(slot_a) = b;
+        *self.indices.get_mut(slot_b) = a;
+        self.entries.swap(a, b);
+    }
+
+    /// Moves the entry at `from` to position `to`, shifting the entries in
+    /// between by one to make room. This is O(n), like `shift_remove`.
+    ///
+    /// Panics if either is out of bounds.
+    pub fn move_index(&mut self, from: usize, to: usize) {
+        assert!(from < self.entries.len() && to < self.entries.len(), "index out of bounds: the len is {}", self.entries.len());
+        for i in self.indices.iter_mut() {
+            if *i == from {
+                *i = to;
+            } else if from < to && (from + 1..=to).contains(i) {
+                *i -= 1;
+            } else if to < from && (to..from).contains(i) {
+                *i += 1;
+            }
+        }
+        if from < to {
+            self.entries[from..=to].rotate_left(1);
+        } else {
+            self.entries[to..=from].rotate_right(1);
+        }
+    }
+
+    /// Sorts the entries by key.
+    pub fn sort_keys(&mut self)
+        where K: Ord,
+    {
+        self.sort_by(|k1, _, k2, _| k1.cmp(k2));
+    }
+
+    /// Sorts the entries with a comparator over both keys and values,
+    /// keeping entries that compare equal in their current order.
+    pub fn sort_by<F>(&mut self, mut cmp: F)
+        where F: FnMut(&K, &V, &K, &V) -> Ordering,
+    {
+        self.entries.sort_by(|a, b| cmp(&a.key, &a.value, &b.key, &b.value));
+        self.rebuild_indices();
+    }
+
+    /// Like `sort_by`, but entries that compare equal may end up in any
+    /// order.
+    pub fn sort_unstable_by<F>(&mut self, mut cmp: F)
+        where F: FnMut(&K, &V, &K, &V) -> Ordering,
+    {
+        self.entries.sort_unstable_by(|a, b| cmp(&a.key, &a.value, &b.key, &b.value));
+        self.rebuild_indices();
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|bucket| (&bucket.key, &bucket.value))
+    }
+
+    /// Iterates in insertion order. This and the other iterators are
+    /// double-ended, so `next_back` and `rev` start from the most recently
+    /// inserted entry.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { entries: self.entries.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { entries: self.entries.iter_mut() }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // Points the index table at the entries' new positions, reusing their
+    // stored hashes.
+    fn rebuild_indices(&mut self) {
+        self.indices.clear();
+        for (index, bucket) in self.entries.iter().enumerate() {
+            self.indices.insert(bucket.hash, index);
+        }
+    }
+
+    // Slot in the index table that points at position `index`.
+    fn slot_of(&self, index: usize) -> usize {
+        self.slot_of_moved(index, index)
+    }
+
+    // Slot in the index table of the entry now at `index`, which the table
+    // still records at position `recorded`.
+    fn slot_of_moved(&self, index: usize, recorded: usize) -> usize {
+        self.indices.find(self.entries[index].hash, |&i| i == recorded).expect("index table out of sync")
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for OrderedHashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // As for `HashMap`: many keys may already be present.
+        let (lower, _) = iter.size_hint();
+        let additional = if self.is_empty() { lower } else { lower.div_ceil(2) };
+        self.reserve(additional);
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for OrderedHashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = OrderedHashMap::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S> fmt::Debug for OrderedHashMap<K, V, S>
+    where K: fmt::Debug,
+          V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    entries: slice::Iter<'a, Bucket<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|bucket| (&bucket.key, &bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|bucket| (&bucket.key, &bucket.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    entries: slice::IterMut<'a, Bucket<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|bucket| (&bucket.key, &mut bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|bucket| (&bucket.key, &mut bucket.value))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+pub struct IntoIter<K, V> {
+    entries: vec::IntoIter<Bucket<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(|bucket| (bucket.key, bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(|bucket| (bucket.key, bucket.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a OrderedHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut OrderedHashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S> IntoIterator for OrderedHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { entries: self.entries.into_iter() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(map: &OrderedHashMap<u32, u32>) -> Vec<u32> {
+        map.keys().copied().collect()
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map: OrderedHashMap<u32, u32> = (0..100).rev().map(|i| (i, i)).collect();
+        assert_eq!(keys(&map), (0..100).rev().collect::<Vec<_>>());
+        assert_eq!(map.insert(50, 500), Some(50));
+        assert_eq!(map.get_index_of(&50), Some(49));
+        assert_eq!(map.get_index(49), Some((&50, &500)));
+        assert_eq!(map.get_index(100), None);
+        assert_eq!((map.first(), map.last()), (Some((&99, &99)), Some((&0, &0))));
+
+        for (_, value) in &mut map {
+            *value += 1;
+        }
+        assert_eq!(map.get(&0), Some(&1));
+        let pairs: Vec<(u32, u32)> = map.into_iter().rev().take(2).collect();
+        assert_eq!(pairs, [(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_swap_and_shift_remove() {
+        let mut map: OrderedHashMap<u32, u32> = (0..6).map(|i| (i, i * 10)).collect();
+        assert_eq!(map.swap_remove(&1), Some(10));
+        assert_eq!(keys(&map), [0, 5, 2, 3, 4]);
+        assert_eq!(map.get(&5), Some(&50));
+        assert_eq!(map.get_index_of(&5), Some(1));
+
+        assert_eq!(map.shift_remove(&0), Some(0));
+        assert_eq!(keys(&map), [5, 2, 3, 4]);
+        assert!((0..4).all(|i| map.get_index_of(map.get_index(i).unwrap().0) == Some(i)));
+
+        assert_eq!(map.swap_remove(&4), Some(40));
+        assert_eq!(map.shift_remove(&4), None);
+        assert_eq!(keys(&map), [5, 2, 3]);
+        assert_eq!(format!("{:?}", map), "{5: 50, 2: 20, 3: 30}");
+    }
+
+    #[test]
+    fn test_positional_moves() {
+        let mut map: OrderedHashMap<u32, u32> = (0..6).map(|i| (i, i * 10)).collect();
+        map.swap_indices(0, 4);
+        assert_eq!(keys(&map), [4, 1, 2, 3, 0, 5]);
+        map.swap_indices(2, 2);
+
+        map.move_index(1, 4);
+        assert_eq!(keys(&map), [4, 2, 3, 0, 1, 5]);
+        map.move_index(5, 0);
+        assert_eq!(keys(&map), [5, 4, 2, 3, 0, 1]);
+        assert!((0..6).all(|i| map.get_index_of(map.get_index(i).unwrap().0) == Some(i)));
+
+        assert_eq!(map.swap_remove_index(1), Some((4, 40)));
+        assert_eq!(map.swap_remove_index(4), Some((0, 0)));
+        assert_eq!(map.swap_remove_index(4), None);
+        assert_eq!(keys(&map), [5, 1, 2, 3]);
+        assert_eq!(map.get_index_of(&1), Some(1));
+        assert_eq!(map.get(&4), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_move_index_out_of_bounds() {
+        let mut map: OrderedHashMap<u32, u32> = (0..3).map(|i| (i, i)).collect();
+        map.move_index(0, 3);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut map: OrderedHashMap<u32, u32> = [(3, 1), (1, 2), (4, 1), (5, 0), (2, 2)].iter().copied().collect();
+        map.sort_keys();
+        assert_eq!(keys(&map), [1, 2, 3, 4, 5]);
+        assert!((1..6).all(|k| map.get_index_of(&k) == Some(k as usize - 1)));
+
+        // Stable: entries with equal values keep their key order.
+        map.sort_by(|_, v1, _, v2| v2.cmp(v1));
+        assert_eq!(keys(&map), [1, 2, 3, 4, 5]);
+        map.sort_by(|_, v1, _, v2| v1.cmp(v2));
+        assert_eq!(keys(&map), [5, 3, 4, 1, 2]);
+
+        map.sort_unstable_by(|k1, _, k2, _| k2.cmp(k1));
+        assert_eq!(keys(&map), [5, 4, 3, 2, 1]);
+        assert_eq!(map.get(&2), Some(&2));
+        assert_eq!(map.get_index_of(&2), Some(3));
+        map.insert(0, 0);
+        assert_eq!(map.last(), Some((&0, &0)));
+    }
+
+    #[test]
+    fn test_lookups_survive_growth() {
+        let mut map = OrderedHashMap::with_capacity(10);
+        assert!(map.capacity() >= 10);
+        for i in 0..1000 {
+            map.insert(i.to_string(), i);
+        }
+        for i in (0..1000).step_by(3) {
+            map.swap_remove(i.to_string().as_str());
+        }
+        assert_eq!(map.len(), 666);
+        assert!(map.iter().all(|(k, v)| *k == v.to_string() && map.get(k.as_str()) == Some(v)));
+        assert!(!map.contains_key("3"));
+    }
+
+    #[test]
+    fn test_double_ended() {
+        let mut map: OrderedHashMap<u32, u32> = (0..5).map(|i| (i, i * 10)).collect();
+
+        let mut iter = map.iter();
+        assert_eq!(iter.next_back(), Some((&4, &40)));
+        assert_eq!(iter.next(), Some((&0, &0)));
+        assert_eq!(iter.next_back(), Some((&3, &30)));
+        assert_eq!(iter.len(), 2);
+        assert_eq!(iter.next(), Some((&1, &10)));
+        assert_eq!(iter.next_back(), Some((&2, &20)));
+        // The two ends have met.
+        assert_eq!((iter.next(), iter.next_back()), (None, None));
+
+        let mut iter = map.iter_mut();
+        *iter.next_back().unwrap().1 += 1;
+        *iter.next().unwrap().1 += 2;
+        *iter.next_back().unwrap().1 += 3;
+        assert_eq!(iter.next().map(|(&key, _)| key), Some(1));
+        assert_eq!(iter.next_back().map(|(&key, _)| key), Some(2));
+        assert!(iter.next().is_none() && iter.next_back().is_none());
+        assert_eq!(map.values().copied().collect::<Vec<_>>(), [2, 10, 20, 33, 41]);
+
+        let mut keys = map.keys();
+        assert_eq!((keys.next_back(), keys.next(), keys.next_back()), (Some(&4), Some(&0), Some(&3)));
+        assert_eq!((keys.next(), keys.next_back(), keys.next()), (Some(&1), Some(&2), None));
+        assert_eq!(keys.next_back(), None);
+
+        let mut values = map.values();
+        assert_eq!((values.next(), values.next_back()), (Some(&2), Some(&41)));
+        assert_eq!(values.len(), 3);
+        assert_eq!(values.rev().collect::<Vec<_>>(), [&33, &20, &10]);
+
+        let newest_first: Vec<u32> = map.keys().rev().copied().collect();
+        assert_eq!(newest_first, [4, 3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,120 @@
+use core::hash::{BuildHasher, Hasher};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// `BuildHasher` whose hashes are the same on every platform and in every
+/// version of this crate, for things like assigning keys to shards across
+/// a mixed fleet of machines.
+///
+/// It is never the default: it has no per-process key, so anyone who can
+/// choose keys can also choose collisions. Only use it where the hashes
+/// must be reproducible.
+///
+/// ```
+/// use core::hash::BuildHasher;
+/// use hashmap::PortableState;
+///
+/// assert_eq!(PortableState.hash_one(42u64), PortableState.hash_one(42usize));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PortableState;
+
+impl BuildHasher for PortableState {
+    type Hasher = PortableHasher;
+
+    fn build_hasher(&self) -> PortableHasher {
+        PortableHasher { state: FNV_OFFSET_BASIS }
+    }
+}
+
+/// 64-bit FNV-1a over the bytes written to it. Integers are fed in
+/// little-endian order and `usize`/`isize` are widened to 64 bits, so the
+/// result doesn't depend on the target's endianness or pointer width.
+///
+/// The guarantee covers the bytes the hasher is given; a key type's `Hash`
+/// impl decides what those are, and should itself be platform-independent.
+#[derive(Debug, Clone)]
+pub struct PortableHasher {
+    state: u64,
+}
+
+impl Default for PortableHasher {
+    fn default() -> Self {
+        PortableState.build_hasher()
+    }
+}
+
+impl Hasher for PortableHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, n: u128) {
+        self.write(&n.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u16(n as u16);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u32(n as u32);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i128(&mut self, n: i128) {
+        self.write_u128(n as u128);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_i64(n as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_values() {
+        // Published FNV-1a 64 test vectors; these must never change.
+        let fnv = |bytes: &[u8]| {
+            let mut hasher = PortableHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(fnv(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv(b"foobar"), 0x8594_4171_f739_67e8);
+
+        assert_eq!(PortableState.hash_one(0x0102_0304u32), fnv(&[4, 3, 2, 1]));
+        assert_eq!(PortableState.hash_one(-1isize), PortableState.hash_one(u64::MAX));
+    }
+}
@@ -0,0 +1,797 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::{self, MaybeUninit};
+use core::slice;
+
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
+
+use crate::{DEFAULT_MAX_LOAD, INITIAL_NBUCKETS};
+
+// Marks a slot as occupied. It is or-ed into every stored hash, so a stored
+// hash is never `EMPTY`.
+const OCCUPIED: u64 = 1 << 63;
+const EMPTY: u64 = 0;
+
+/// Open-addressing table with Robin Hood linear probing, holding entries of
+/// any type with their full hashes stored alongside.
+///
+/// Each entry sits at or after its home slot, `hash & (slots - 1)`, and on
+/// insert an entry further from home takes the slot of one nearer to its
+/// own, which keeps probe lengths short and even. Removal shifts the rest of
+/// the cluster back by one instead of leaving tombstones. Positions are
+/// plain slot indices; they stay valid until the table is next modified.
+///
+/// The slot count is zero or a power of two. Callers decide when to grow,
+/// normally by checking [`needs_grow`](RawTable::needs_grow) before each
+/// insert, and must leave at least one slot free.
+///
+/// During an incremental resize (see [`begin_resize`](RawTable::begin_resize))
+/// the table being emptied stays behind this one: its slots are numbered
+/// from `slots()` on, up to `positions()`, and lookups, removal and
+/// iteration cover both. New entries always go into this table.
+pub(crate) struct RawTable<T> {
+    hashes: Vec<u64>,
+    entries: Vec<MaybeUninit<T>>,
+    items: usize,
+    max_load: f64,
+    old: Option<Box<Migration<T>>>,
+}
+
+// The table an incremental resize is moving entries out of. Every slot
+// before `cursor` has been emptied.
+struct Migration<T> {
+    table: RawTable<T>,
+    cursor: usize,
+}
+
+impl<T> RawTable<T> {
+    pub(crate) fn new() -> Self {
+        Self::with_slots(0)
+    }
+
+    pub(crate) fn with_slots(slots: usize) -> Self {
+        debug_assert!(slots == 0 || slots.is_power_of_two());
+        let mut entries = Vec::with_capacity(slots);
+        entries.resize_with(slots, MaybeUninit::uninit);
+        RawTable { hashes: vec![EMPTY; slots], entries, items: 0, max_load: DEFAULT_MAX_LOAD, old: None }
+    }
+
+    /// Entries in the table, counting any still in the old one.
+    pub(crate) fn len(&self) -> usize {
+        self.items + self.old.as_ref().map_or(0, |old| old.table.items)
+    }
+
+    pub(crate) fn slots(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Number of positions: this table's slots followed by the old table's.
+    pub(crate) fn positions(&self) -> usize {
+        self.slots() + self.old.as_ref().map_or(0, |old| old.table.slots())
+    }
+
+    /// Sets the fraction of slots, strictly between 0 and 1, that may fill
+    /// before `needs_grow` says to grow.
+    pub(crate) fn set_max_load(&mut self, max_load: f64) {
+        debug_assert!(max_load > 0.0 && max_load < 1.0);
+        self.max_load = max_load;
+    }
+
+    /// Whether one more entry would take the table past its load factor,
+    /// 3/4 unless set otherwise, so that it should grow first.
+    pub(crate) fn needs_grow(&self) -> bool {
+        self.slots() == 0 || self.len() > self.limit(self.slots())
+    }
+
+    /// How many entries fit before `needs_grow` says to grow.
+    pub(crate) fn capacity(&self) -> usize {
+        match self.slots() {
+            0 => 0,
+            n => self.limit(n) + 1,
+        }
+    }
+
+    /// Slot count to grow to: double the current one.
+    pub(crate) fn grown_slots(&self) -> usize {
+        match self.slots() {
+            0 => INITIAL_NBUCKETS,
+            n => 2 * n,
+        }
+    }
+
+    /// Smallest slot count that holds `len` entries without `needs_grow`
+    /// asking for more.
+    pub(crate) fn slots_for(&self, len: usize) -> usize {
+        let mut slots = INITIAL_NBUCKETS;
+        while self.limit(slots) < len.saturating_sub(1) {
+            slots *= 2;
+        }
+        slots
+    }
+
+    /// Reallocates with `slots` slots and moves every entry across,
+    /// finishing any incremental resize on the way.
+    pub(crate) fn resize(&mut self, slots: usize) {
+        let mut table = RawTable::with_slots(slots);
+        table.max_load = self.max_load;
+        self.move_into(&mut table);
+        *self = table;
+    }
+
+    /// Starts an incremental resize to `slots` slots: allocates the new
+    /// table but leaves the entries in the old one, to be moved across by
+    /// [`migrate`](RawTable::migrate).
+    ///
+    /// Panics if a resize is already under way.
+    pub(crate) fn begin_resize(&mut self, slots: usize) {
+        assert!(self.old.is_none(), "a resize is already under way");
+        let mut table = RawTable::with_slots(slots);
+        table.max_load = self.max_load;
+        let old = mem::replace(self, table);
+        self.old = Some(Box::new(Migration { table: old, cursor: 0 }));
+    }
+
+    /// Moves entries across from the old table, visiting at most `slots` of
+    /// its slots and calling `check` with the old table and the slot of each
+    /// entry before it moves. Once it is empty the old table is handed back,
+    /// still allocated, for the caller to free.
+    pub(crate) fn migrate(&mut self, slots: usize, mut check: impl FnMut(&RawTable<T>, usize)) -> Option<RawTable<T>> {
+        let mut migration = self.old.take()?;
+        for _ in 0..slots {
+            let old = &mut migration.table;
+            if old.items == 0 {
+                break;
+            }
+            if old.is_occupied(migration.cursor) {
+                check(old, migration.cursor);
+                // The rest of its cluster shifts back into the cursor's
+                // slot but never before it, as with `pop_from`.
+                let (hash, entry) = old.take(migration.cursor);
+                self.insert(hash, entry);
+            } else {
+                migration.cursor += 1;
+            }
+        }
+        if migration.table.items == 0 {
+            return Some(migration.table);
+        }
+        self.old = Some(migration);
+        None
+    }
+
+    /// Bytes allocated for the slots, as reported to allocation hooks.
+    pub(crate) fn alloc_bytes(&self) -> usize {
+        self.hashes.capacity() * mem::size_of::<u64>()
+            + self.entries.capacity() * mem::size_of::<T>()
+            + self.old.as_ref().map_or(0, |old| old.table.alloc_bytes())
+    }
+
+    pub(crate) fn is_occupied(&self, index: usize) -> bool {
+        let (table, index) = self.locate(index);
+        table.hashes[index] != EMPTY
+    }
+
+    /// Whether the entry at `index` was inserted with `hash`.
+    #[cfg(feature = "verify-hash")]
+    pub(crate) fn has_hash(&self, index: usize, hash: u64) -> bool {
+        let (table, index) = self.locate(index);
+        table.hashes[index] == hash | OCCUPIED
+    }
+
+    pub(crate) fn get(&self, index: usize) -> &T {
+        assert!(self.is_occupied(index), "slot {} is empty", index);
+        let (table, index) = self.locate(index);
+        // SAFETY: a stored hash says the slot is initialized.
+        unsafe { table.entries[index].assume_init_ref() }
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> &mut T {
+        assert!(self.is_occupied(index), "slot {} is empty", index);
+        let slots = self.slots();
+        if let Some(old) = self.old.as_mut().filter(|_| index >= slots) {
+            return old.table.get_mut(index - slots);
+        }
+        // SAFETY: a stored hash says the slot is initialized.
+        unsafe { self.entries[index].assume_init_mut() }
+    }
+
+    /// Mutable references to two different entries.
+    pub(crate) fn get_pair_mut(&mut self, a: usize, b: usize) -> (&mut T, &mut T) {
+        assert!(a != b, "slot {} requested twice", a);
+        assert!(self.is_occupied(a) && self.is_occupied(b), "slot is empty");
+        let slots = self.slots();
+        if let Some(old) = &mut self.old {
+            // SAFETY, for each arm: both slots hold stored hashes, so both
+            // are initialized.
+            match (a < slots, b < slots) {
+                (false, false) => return old.table.get_pair_mut(a - slots, b - slots),
+                (true, false) => return (unsafe { self.entries[a].assume_init_mut() }, old.table.get_mut(b - slots)),
+                (false, true) => return (old.table.get_mut(a - slots), unsafe { self.entries[b].assume_init_mut() }),
+                (true, true) => {}
+            }
+        }
+        let (lo, hi) = (a.min(b), a.max(b));
+        let (head, tail) = self.entries.split_at_mut(hi);
+        // SAFETY: both slots hold stored hashes, so both are initialized.
+        let (lo, hi) = unsafe { (head[lo].assume_init_mut(), tail[0].assume_init_mut()) };
+        if a < b { (lo, hi) } else { (hi, lo) }
+    }
+
+    /// Positions holding entries whose hashes could equal `hash`, in probe
+    /// order, this table's before the old one's. Each table's probe stops at
+    /// an empty slot or at an entry nearer to its home than `hash` would be,
+    /// since Robin Hood insertion would have put a matching entry before
+    /// either.
+    pub(crate) fn probe(&self, hash: u64) -> impl Iterator<Item = usize> + '_ {
+        let slots = self.slots();
+        let old = self.old.iter().flat_map(move |old| old.table.probe_slots(hash).map(move |index| slots + index));
+        self.probe_slots(hash).chain(old)
+    }
+
+    pub(crate) fn find(&self, hash: u64, mut eq: impl FnMut(&T) -> bool) -> Option<usize> {
+        let stored = hash | OCCUPIED;
+        self.probe(hash).find(|&index| {
+            let (table, index) = self.locate(index);
+            table.hashes[index] == stored && eq(table.get(index))
+        })
+    }
+
+    /// Inserts an entry that is known to be absent, returning its slot and
+    /// the longest probe length among the entries it placed or displaced.
+    ///
+    /// Panics if the table is full.
+    pub(crate) fn insert(&mut self, hash: u64, entry: T) -> (usize, usize) {
+        assert!(self.items < self.slots(), "no free slot in the table");
+        let mut hash = hash | OCCUPIED;
+        let mut entry = entry;
+        let mut pos = self.home(hash);
+        let mut dist = 0;
+        let mut placed = None;
+        let mut longest = 0;
+        loop {
+            if self.hashes[pos] == EMPTY {
+                self.hashes[pos] = hash;
+                self.entries[pos].write(entry);
+                self.items += 1;
+                return (placed.unwrap_or(pos), longest.max(dist + 1));
+            }
+            let resident = self.dist(pos);
+            if resident < dist {
+                // Take the slot from the richer resident and carry it on.
+                mem::swap(&mut self.hashes[pos], &mut hash);
+                // SAFETY: the slot held a stored hash, so it is initialized.
+                mem::swap(unsafe { self.entries[pos].assume_init_mut() }, &mut entry);
+                placed.get_or_insert(pos);
+                longest = longest.max(dist + 1);
+                dist = resident;
+            }
+            pos = (pos + 1) & self.mask();
+            dist += 1;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, index: usize) -> T {
+        self.take(index).1
+    }
+
+    /// `remove`, also returning the hash the entry was inserted with.
+    pub(crate) fn take(&mut self, index: usize) -> (u64, T) {
+        assert!(self.is_occupied(index), "slot {} is empty", index);
+        let slots = self.slots();
+        if let Some(old) = self.old.as_mut().filter(|_| index >= slots) {
+            return old.table.take(index - slots);
+        }
+        let hash = self.hashes[index] & !OCCUPIED;
+        // SAFETY: the slot is initialized; it is either refilled by the
+        // shift below or marked empty, so it won't be read again as is.
+        let entry = unsafe { self.entries[index].assume_init_read() };
+        let mut hole = index;
+        loop {
+            let next = (hole + 1) & self.mask();
+            if next == index || self.hashes[next] == EMPTY || self.dist(next) == 0 {
+                break;
+            }
+            self.hashes[hole] = self.hashes[next];
+            self.entries.swap(hole, next);
+            hole = next;
+        }
+        self.hashes[hole] = EMPTY;
+        wipe(slice::from_mut(&mut self.entries[hole]));
+        self.items -= 1;
+        (hash, entry)
+    }
+
+    /// Removes an entry from the first occupied position at or after
+    /// `*cursor`, returning it with its hash as `take` does and leaving
+    /// `*cursor` there for the next call. Removing shifts later entries back
+    /// but never past the cursor, so repeated calls empty the table.
+    pub(crate) fn pop_from(&mut self, cursor: &mut usize) -> Option<(u64, T)> {
+        while *cursor < self.positions() {
+            if self.is_occupied(*cursor) {
+                return Some(self.take(*cursor));
+            }
+            *cursor += 1;
+        }
+        None
+    }
+
+    /// Visits every entry once, removing those for which `keep` returns false.
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&mut T) -> bool) {
+        let mut scan = self.scan();
+        while self.remove_next(&mut scan, |entry| !keep(entry)).is_some() {}
+    }
+
+    /// Starts a pass for [`remove_next`](RawTable::remove_next).
+    pub(crate) fn scan(&self) -> Scan {
+        let old = self.old.as_ref().map_or(Pass { index: 0, visited: 0 }, |old| old.table.pass());
+        Scan { own: self.pass(), old }
+    }
+
+    /// Continues the pass, visiting entries until `remove` picks one, and
+    /// returns it. Each entry present when the pass started is visited once,
+    /// provided the table isn't otherwise modified in between.
+    pub(crate) fn remove_next(&mut self, scan: &mut Scan, mut remove: impl FnMut(&mut T) -> bool) -> Option<T> {
+        if let Some(entry) = self.remove_next_in_pass(&mut scan.own, &mut remove) {
+            return Some(entry);
+        }
+        self.old.as_mut()?.table.remove_next_in_pass(&mut scan.old, remove)
+    }
+
+    // Start a pass over this table's slots at one no cluster wraps into, so
+    // a removal near the end never shifts an already visited entry back
+    // into view.
+    fn pass(&self) -> Pass {
+        let start = (0..self.slots())
+            .find(|&index| !self.is_occupied(index) || self.dist(index) == 0)
+            .unwrap_or(0);
+        Pass { index: start, visited: 0 }
+    }
+
+    fn remove_next_in_pass(&mut self, pass: &mut Pass, mut remove: impl FnMut(&mut T) -> bool) -> Option<T> {
+        while pass.visited < self.slots() {
+            let index = pass.index;
+            if self.is_occupied(index) && remove(self.get_mut(index)) {
+                // The next entry of the cluster moves into `index`, so the
+                // pass stays put.
+                return Some(self.remove(index));
+            }
+            pass.index = (index + 1) & self.mask();
+            pass.visited += 1;
+        }
+        None
+    }
+
+    /// Moves every entry into `dest`, reusing the stored hashes.
+    fn move_into(&mut self, dest: &mut RawTable<T>) {
+        if let Some(mut old) = self.old.take() {
+            old.table.move_into(dest);
+        }
+        for index in 0..self.slots() {
+            if self.is_occupied(index) {
+                let hash = mem::replace(&mut self.hashes[index], EMPTY) & !OCCUPIED;
+                // SAFETY: the slot was initialized and is now marked empty.
+                dest.insert(hash, unsafe { self.entries[index].assume_init_read() });
+            }
+        }
+        wipe(&mut self.entries);
+        self.items = 0;
+    }
+
+    /// Drops every entry. The old table of an incremental resize is emptied
+    /// too but stays allocated until `migrate` hands it back.
+    pub(crate) fn clear(&mut self) {
+        for index in 0..self.slots() {
+            if mem::replace(&mut self.hashes[index], EMPTY) != EMPTY {
+                // SAFETY: the slot was initialized and is now marked empty.
+                unsafe { self.entries[index].assume_init_drop() };
+                wipe(slice::from_mut(&mut self.entries[index]));
+            }
+        }
+        self.items = 0;
+        if let Some(old) = &mut self.old {
+            old.table.clear();
+        }
+    }
+
+    /// Entries at positions `start..end`, in position order.
+    pub(crate) fn iter_range(&self, start: usize, end: usize) -> RawIter<'_, T> {
+        RawIter { table: self, pos: start, end }
+    }
+
+    pub(crate) fn iter(&self) -> RawIter<'_, T> {
+        self.iter_range(0, self.positions())
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> RawIterMut<'_, T> {
+        let old = self.old.as_mut().map(|old| SlotsMut {
+            hashes: old.table.hashes.iter(),
+            entries: old.table.entries.iter_mut(),
+        });
+        RawIterMut { slots: SlotsMut { hashes: self.hashes.iter(), entries: self.entries.iter_mut() }, old }
+    }
+
+    /// Moves the entries out in position order. The table counts as empty
+    /// from here on; entries the iterator never reaches stay in their slots
+    /// and are dropped with the table.
+    #[cfg(feature = "rayon")]
+    pub(crate) fn drain_slots(&mut self) -> RawDrain<'_, T> {
+        self.items = 0;
+        let old = self.old.as_mut().map(|old| {
+            old.table.items = 0;
+            DrainSlots { hashes: old.table.hashes.iter_mut(), entries: old.table.entries.iter_mut() }
+        });
+        RawDrain { slots: DrainSlots { hashes: self.hashes.iter_mut(), entries: self.entries.iter_mut() }, old }
+    }
+
+    /// Longest probe length of any entry: one more than its distance from
+    /// its home slot.
+    pub(crate) fn longest_probe(&self) -> usize {
+        let own = (0..self.slots())
+            .filter(|&index| self.is_occupied(index))
+            .map(|index| self.dist(index) + 1)
+            .max()
+            .unwrap_or(0);
+        own.max(self.old.as_ref().map_or(0, |old| old.table.longest_probe()))
+    }
+
+    /// The table holding `index`, and its slot there.
+    pub(crate) fn locate(&self, index: usize) -> (&RawTable<T>, usize) {
+        match &self.old {
+            Some(old) if index >= self.slots() => (&old.table, index - self.slots()),
+            _ => (self, index),
+        }
+    }
+
+    // Entries allowed in `slots` slots, keeping one free.
+    fn limit(&self, slots: usize) -> usize {
+        ((slots as f64 * self.max_load) as usize).min(slots.saturating_sub(1))
+    }
+
+    // The slots of this table alone that `probe` visits.
+    fn probe_slots(&self, hash: u64) -> Probe<'_, T> {
+        let pos = match self.slots() {
+            0 => 0,
+            _ => self.home(hash),
+        };
+        Probe { table: self, pos, dist: 0 }
+    }
+
+    fn mask(&self) -> usize {
+        self.slots() - 1
+    }
+
+    fn home(&self, hash: u64) -> usize {
+        hash as usize & self.mask()
+    }
+
+    // How far the entry at `index` sits past its home slot.
+    fn dist(&self, index: usize) -> usize {
+        index.wrapping_sub(self.home(self.hashes[index])) & self.mask()
+    }
+}
+
+/// Clones entry by entry into the same slots, so nothing is rehashed.
+impl<T: Clone> Clone for RawTable<T> {
+    fn clone(&self) -> Self {
+        let mut table = RawTable::with_slots(self.slots());
+        table.max_load = self.max_load;
+        for index in 0..self.slots() {
+            if self.is_occupied(index) {
+                // Marked occupied only once written, in case `clone` panics.
+                table.entries[index].write(self.get(index).clone());
+                table.hashes[index] = self.hashes[index];
+                table.items += 1;
+            }
+        }
+        table.old = self.old.as_ref().map(|old| Box::new(Migration { table: old.table.clone(), cursor: old.cursor }));
+        table
+    }
+}
+
+impl<T> Drop for RawTable<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+// With the `zeroize` feature, slots that no longer hold a live entry are
+// wiped before they are reused or freed. Without it this is a no-op.
+#[cfg(feature = "zeroize")]
+fn wipe<T>(slots: &mut [MaybeUninit<T>]) {
+    slots.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+fn wipe<T>(_slots: &mut [MaybeUninit<T>]) {}
+
+/// Position of a pass started by [`RawTable::scan`].
+pub(crate) struct Scan {
+    own: Pass,
+    old: Pass,
+}
+
+// How far a pass has got through one table's slots.
+struct Pass {
+    index: usize,
+    visited: usize,
+}
+
+/// Iterator returned by [`RawTable::probe_slots`].
+pub(crate) struct Probe<'a, T> {
+    table: &'a RawTable<T>,
+    pos: usize,
+    dist: usize,
+}
+
+impl<'a, T> Iterator for Probe<'a, T> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let table = self.table;
+        if self.dist == table.slots() || !table.is_occupied(self.pos) || table.dist(self.pos) < self.dist {
+            return None;
+        }
+        let index = self.pos;
+        self.pos = (self.pos + 1) & table.mask();
+        self.dist += 1;
+        Some(index)
+    }
+}
+
+pub(crate) struct RawIter<'a, T> {
+    table: &'a RawTable<T>,
+    pos: usize,
+    end: usize,
+}
+
+impl<'a, T> Iterator for RawIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        while self.pos < self.end {
+            let index = self.pos;
+            self.pos += 1;
+            if self.table.is_occupied(index) {
+                return Some(self.table.get(index));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T> RawIter<'a, T> {
+    /// Splits the remaining positions in half, unless there is only one
+    /// left.
+    pub(crate) fn split(self) -> (Self, Option<Self>) {
+        if self.end - self.pos < 2 {
+            return (self, None);
+        }
+        let mid = self.pos + (self.end - self.pos) / 2;
+        let back = RawIter { table: self.table, pos: mid, end: self.end };
+        (RawIter { end: mid, ..self }, Some(back))
+    }
+}
+
+pub(crate) struct RawIterMut<'a, T> {
+    slots: SlotsMut<'a, T>,
+    old: Option<SlotsMut<'a, T>>,
+}
+
+struct SlotsMut<'a, T> {
+    hashes: slice::Iter<'a, u64>,
+    entries: slice::IterMut<'a, MaybeUninit<T>>,
+}
+
+impl<'a, T> Iterator for RawIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.slots.next().or_else(|| self.old.as_mut()?.next())
+    }
+}
+
+impl<'a, T> Iterator for SlotsMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<&'a mut T> {
+        loop {
+            let (&hash, entry) = (self.hashes.next()?, self.entries.next()?);
+            if hash != EMPTY {
+                // SAFETY: a stored hash says the slot is initialized.
+                return Some(unsafe { entry.assume_init_mut() });
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T> RawIterMut<'a, T> {
+    /// Splits the old table's slots from this one's, or else the remaining
+    /// slots in half, unless there is only one left.
+    pub(crate) fn split(self) -> (Self, Option<Self>) {
+        if let Some(old) = self.old {
+            return (RawIterMut { slots: self.slots, old: None }, Some(RawIterMut { slots: old, old: None }));
+        }
+        let hashes = self.slots.hashes.as_slice();
+        if hashes.len() < 2 {
+            return (self, None);
+        }
+        let mid = hashes.len() / 2;
+        let (front_hashes, back_hashes) = hashes.split_at(mid);
+        let (front, back) = self.slots.entries.into_slice().split_at_mut(mid);
+        let half = |hashes: &'a [u64], entries: &'a mut [MaybeUninit<T>]| RawIterMut {
+            slots: SlotsMut { hashes: hashes.iter(), entries: entries.iter_mut() },
+            old: None,
+        };
+        (half(front_hashes, front), Some(half(back_hashes, back)))
+    }
+}
+
+/// Iterator returned by [`RawTable::drain_slots`].
+#[cfg(feature = "rayon")]
+pub(crate) struct RawDrain<'a, T> {
+    slots: DrainSlots<'a, T>,
+    old: Option<DrainSlots<'a, T>>,
+}
+
+#[cfg(feature = "rayon")]
+struct DrainSlots<'a, T> {
+    hashes: slice::IterMut<'a, u64>,
+    entries: slice::IterMut<'a, MaybeUninit<T>>,
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T> Iterator for RawDrain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.slots.next().or_else(|| self.old.as_mut()?.next())
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T> Iterator for DrainSlots<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let (hash, entry) = (self.hashes.next()?, self.entries.next()?);
+            if mem::replace(hash, EMPTY) != EMPTY {
+                // SAFETY: the slot was initialized and is now marked empty.
+                let value = unsafe { entry.assume_init_read() };
+                wipe(slice::from_mut(entry));
+                return Some(value);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T> RawDrain<'a, T> {
+    /// Splits the old table's slots from this one's, or else the remaining
+    /// slots in half, unless there is only one left.
+    pub(crate) fn split(self) -> (Self, Option<Self>) {
+        if let Some(old) = self.old {
+            return (RawDrain { slots: self.slots, old: None }, Some(RawDrain { slots: old, old: None }));
+        }
+        let hashes = self.slots.hashes.into_slice();
+        let entries = self.slots.entries.into_slice();
+        let half = |hashes: &'a mut [u64], entries: &'a mut [MaybeUninit<T>]| RawDrain {
+            slots: DrainSlots { hashes: hashes.iter_mut(), entries: entries.iter_mut() },
+            old: None,
+        };
+        if hashes.len() < 2 {
+            return (half(hashes, entries), None);
+        }
+        let mid = hashes.len() / 2;
+        let (front_hashes, back_hashes) = hashes.split_at_mut(mid);
+        let (front, back) = entries.split_at_mut(mid);
+        (half(front_hashes, front), Some(half(back_hashes, back)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_wraps_around() {
+        // Every entry's home is the last slot, so the cluster wraps.
+        let mut table = RawTable::with_slots(8);
+        for i in 0..6 {
+            table.insert(7, i);
+        }
+        assert_eq!(table.longest_probe(), 6);
+        assert_eq!(table.find(7, |&v| v == 5), Some(4));
+        assert_eq!(table.find(7, |&v| v == 6), None);
+
+        assert_eq!(table.remove(7), 0);
+        let mut rest: Vec<i32> = table.iter().copied().collect();
+        rest.sort_unstable();
+        assert_eq!(rest, [1, 2, 3, 4, 5]);
+        assert!((1..6).all(|i| table.find(7, |&v| v == i).is_some()));
+    }
+
+    #[test]
+    fn test_robin_hood_displacement() {
+        let mut table = RawTable::with_slots(8);
+        table.insert(0, "a");
+        table.insert(0, "b");
+        // "c" is at home in slot 1 but "b" got there first; "c" goes on past
+        // it, being no further from home than "b" is.
+        assert_eq!(table.insert(1, "c"), (2, 2));
+        // "d" belongs in slot 0 too, and is poorer than "c" by the time it
+        // reaches slot 2, so it takes that slot and "c" moves on.
+        assert_eq!(table.insert(0, "d"), (2, 3));
+        assert_eq!(table.find(1, |&v| v == "c"), Some(3));
+
+        let mut kept = 0;
+        table.retain(|v| {
+            kept += 1;
+            *v != "a"
+        });
+        assert_eq!(kept, 4);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.find(0, |&v| v == "d"), Some(1));
+        assert_eq!(table.find(1, |&v| v == "c"), Some(2));
+    }
+
+    #[test]
+    fn test_incremental_migration() {
+        // Entries 0 and 1 wrap around from the last slot into the first.
+        let entries = [(7, 0), (7, 1), (3, 3), (3, 4), (5, 5)];
+        let mut table = RawTable::with_slots(8);
+        for &(hash, value) in &entries {
+            table.insert(hash, value);
+        }
+        table.begin_resize(16);
+        assert_eq!((table.len(), table.positions()), (5, 24));
+        table.insert(9, 9);
+        assert!(table.migrate(2, |_, _| {}).is_none());
+
+        // Found wherever they are, the old table's positions after the new
+        // one's.
+        assert_eq!(table.len(), 6);
+        assert!(table.find(3, |&v| v == 4).unwrap() >= 16);
+        assert_eq!(table.find(9, |&v| v == 9), Some(9));
+        assert!(entries.iter().all(|&(hash, value)| table.find(hash, |&v| v == value).is_some()));
+        let index = table.find(7, |&v| v == 1).unwrap();
+        assert_eq!(table.remove(index), 1);
+        let mut values: Vec<i32> = table.iter().copied().collect();
+        values.sort_unstable();
+        assert_eq!(values, [0, 3, 4, 5, 9]);
+
+        table.retain(|&mut v| v != 4);
+        let mut checked = Vec::new();
+        let old = loop {
+            if let Some(old) = table.migrate(1, |old, slot| checked.push(*old.get(slot))) {
+                break old;
+            }
+        };
+        checked.sort_unstable();
+        assert_eq!(checked, [0, 3, 5]);
+        assert_eq!((old.len(), old.slots()), (0, 8));
+        assert_eq!((table.len(), table.positions()), (4, 16));
+        assert_eq!(table.find(7, |&v| v == 0).map(|i| table.dist(i)), Some(0));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_wipes_removed_entries() {
+        let mut map = crate::HashMap::new();
+        map.insert(1u64, u64::MAX);
+        map.insert(2u64, u64::MAX);
+        map.remove(&1);
+        map.remove(&2);
+
+        for slot in &map.table.entries {
+            let bytes = unsafe {
+                std::slice::from_raw_parts(slot.as_ptr() as *const u8, mem::size_of_val(slot))
+            };
+            assert!(bytes.iter().all(|&b| b == 0));
+        }
+    }
+}
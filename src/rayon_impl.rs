@@ -0,0 +1,218 @@
+use alloc::collections::LinkedList;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+use rayon::iter::plumbing::{bridge_unindexed, Folder, UnindexedConsumer, UnindexedProducer};
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::raw::{RawDrain, RawIter, RawIterMut};
+use crate::HashMap;
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Parallel version of `iter`. Rayon splits the table's buckets into
+    /// ranges, so the work divides evenly however the entries are spread.
+    pub fn par_iter(&self) -> ParIter<'_, K, V> {
+        ParIter { inner: self.table.iter() }
+    }
+
+    /// Parallel version of `iter_mut`.
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, K, V> {
+        ParIterMut { inner: self.table.iter_mut() }
+    }
+}
+
+/// Parallel iterator returned by [`HashMap::par_iter`].
+pub struct ParIter<'a, K, V> {
+    inner: RawIter<'a, (K, V)>,
+}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K: Sync, V: Sync> UnindexedProducer for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let (front, back) = self.inner.split();
+        (ParIter { inner: front }, back.map(|inner| ParIter { inner }))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        folder.consume_iter(self.inner.map(|(key, value)| (key, value)))
+    }
+}
+
+/// Parallel iterator returned by [`HashMap::par_iter_mut`].
+pub struct ParIterMut<'a, K, V> {
+    inner: RawIterMut<'a, (K, V)>,
+}
+
+// SAFETY: it only hands out `&K` and `&mut V`, so sending it to another
+// thread shares the keys and sends the values, nothing more.
+unsafe impl<K: Sync, V: Send> Send for ParIterMut<'_, K, V> {}
+
+impl<'a, K: Sync, V: Send> ParallelIterator for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge_unindexed(self, consumer)
+    }
+}
+
+impl<'a, K: Sync, V: Send> UnindexedProducer for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let (front, back) = self.inner.split();
+        (ParIterMut { inner: front }, back.map(|inner| ParIterMut { inner }))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        folder.consume_iter(self.inner.map(|(key, value)| (&*key, value)))
+    }
+}
+
+/// Parallel iterator returned by `HashMap::into_par_iter`.
+pub struct IntoParIter<K, V, S> {
+    map: HashMap<K, V, S>,
+}
+
+impl<K: Send, V: Send, S: Send> ParallelIterator for IntoParIter<K, V, S> {
+    type Item = (K, V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(mut self, consumer: C) -> C::Result {
+        // Entries a short-circuiting consumer leaves behind are dropped
+        // with the map.
+        bridge_unindexed(DrainProducer { inner: self.map.table.drain_slots() }, consumer)
+    }
+}
+
+struct DrainProducer<'a, K, V> {
+    inner: RawDrain<'a, (K, V)>,
+}
+
+impl<'a, K: Send, V: Send> UnindexedProducer for DrainProducer<'a, K, V> {
+    type Item = (K, V);
+
+    fn split(self) -> (Self, Option<Self>) {
+        let (front, back) = self.inner.split();
+        (DrainProducer { inner: front }, back.map(|inner| DrainProducer { inner }))
+    }
+
+    fn fold_with<F: Folder<Self::Item>>(self, folder: F) -> F {
+        folder.consume_iter(self.inner)
+    }
+}
+
+impl<K: Send, V: Send, S: Send> IntoParallelIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type Iter = IntoParIter<K, V, S>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        IntoParIter { map: self }
+    }
+}
+
+impl<'a, K: Sync, V: Sync, S> IntoParallelIterator for &'a HashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type Iter = ParIter<'a, K, V>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, K: Sync, V: Send, S> IntoParallelIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type Iter = ParIterMut<'a, K, V>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+/// Collects each thread's share into its own `Vec`, then inserts them in
+/// order into a map sized for all of them, so a repeated key keeps its last
+/// value as with `collect`.
+impl<K, V, S> FromParallelIterator<(K, V)> for HashMap<K, V, S>
+    where K: Hash + Eq + Send,
+          V: Send,
+          S: BuildHasher + Default,
+{
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let chunks: LinkedList<Vec<(K, V)>> = par_iter
+            .into_par_iter()
+            .fold(Vec::new, |mut chunk, entry| {
+                chunk.push(entry);
+                chunk
+            })
+            .map(|chunk| {
+                let mut list = LinkedList::new();
+                list.push_back(chunk);
+                list
+            })
+            .reduce(LinkedList::new, |mut front, mut back| {
+                front.append(&mut back);
+                front
+            });
+        let len = chunks.iter().map(Vec::len).sum();
+        let mut map = HashMap::with_capacity_and_hasher(len, S::default());
+        for chunk in chunks {
+            map.extend(chunk);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_par_iter() {
+        let mut map: HashMap<u64, u64> = (0..10_000).map(|i| (i, i)).collect();
+        assert_eq!(map.par_iter().map(|(k, v)| k + v).sum::<u64>(), 2 * (0..10_000).sum::<u64>());
+        assert_eq!(map.par_iter().count(), 10_000);
+
+        map.par_iter_mut().for_each(|(k, v)| *v = k * 3);
+        assert!(map.iter().all(|(k, v)| *v == k * 3));
+        assert_eq!((&map).into_par_iter().filter(|(_, v)| **v % 2 == 0).count(), 5_000);
+    }
+
+    #[test]
+    fn test_into_par_iter_and_collect() {
+        let map: HashMap<u32, String> = (0..5_000u32).into_par_iter().map(|i| (i % 4_000, i.to_string())).collect();
+        assert_eq!(map.len(), 4_000);
+        // The later of two values for a key wins, as with `collect`.
+        assert_eq!(map.get(&10).map(String::as_str), Some("4010"));
+        assert_eq!(map.capacity_report().resizes, 1);
+
+        let mut pairs: Vec<(u32, String)> = map.into_par_iter().collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs.len(), 4_000);
+        assert!(pairs.iter().enumerate().all(|(i, (k, _))| *k == i as u32));
+
+        // Stopping early drops whatever wasn't yielded exactly once.
+        let live = AtomicUsize::new(0);
+        struct Counted<'a>(&'a AtomicUsize);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        let map: HashMap<u32, Counted<'_>> = (0..1_000)
+            .map(|i| {
+                live.fetch_add(1, Ordering::SeqCst);
+                (i, Counted(&live))
+            })
+            .collect();
+        assert!(map.into_par_iter().find_any(|(k, _)| *k == 500).is_some());
+        assert_eq!(live.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,397 @@
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
+
+use crate::{make_hash, Drain, Entry, Equivalent, HashMap, KeyNotFound, SeededState};
+
+/// One mutating call made through a [`RecordingMap`]. Keys and values are
+/// only present if the map was made with [`RecordingMap::capturing`]; the
+/// fixed-key hashes always are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    /// `insert`, the destination side of `transfer`, or an `entry` call
+    /// that left a value under the key.
+    Insert { hash: u64, key: Option<K>, value: Option<V> },
+    Replace { hash: u64, key: Option<K>, value: Option<V> },
+    /// `remove`, the source side of `transfer`, or an `entry` call that
+    /// left no value under the key.
+    Remove { hash: u64, key: Option<K> },
+    /// The keys a `retain` call dropped.
+    Retain { hashes: Vec<u64>, keys: Option<Vec<K>> },
+    /// `clear` or `drain`.
+    Clear,
+}
+
+/// A recorded [`Op`] together with the map's shape right after the call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry<K, V> {
+    pub op: Op<K, V>,
+    pub len: usize,
+    pub buckets: usize,
+}
+
+/// Every mutation of a [`RecordingMap`], in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<K, V> {
+    pub entries: Vec<TraceEntry<K, V>>,
+}
+
+/// Returned by [`replay`] when a trace can't be re-run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Re-running the step did not reproduce the recorded hashes, length
+    /// or bucket count.
+    Diverged { step: usize },
+    /// The step was recorded without its keys or values.
+    NotCaptured { step: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReplayError::Diverged { step } => write!(f, "replay diverged from the trace at step {}", step),
+            ReplayError::NotCaptured { step } => write!(f, "step {} of the trace was recorded without its keys", step),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+/// A [`HashMap`] that appends each mutating call to a [`Trace`].
+///
+/// By default only hashes and the map's length and bucket count are
+/// recorded, which is enough to see how a map reached its shape and needs
+/// nothing of `K` or `V`. A map made with [`capturing`] also clones every
+/// key and value into the trace, so the map can be rebuilt with [`replay`].
+///
+/// Reads go through `Deref` and are not recorded.
+///
+/// [`capturing`]: RecordingMap::capturing
+pub struct RecordingMap<K, V> {
+    map: HashMap<K, V>,
+    trace: Trace<K, V>,
+    capture: Option<Capture<K, V>>,
+}
+
+// Clones keys and values into the trace, for maps made by `capturing`.
+struct Capture<K, V> {
+    key: fn(&K) -> K,
+    value: fn(&V) -> V,
+}
+
+impl<K, V> Default for RecordingMap<K, V>
+    where K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> RecordingMap<K, V>
+    where K: Hash + Eq + Clone,
+          V: Clone,
+{
+    /// A map whose trace also holds a copy of every key and value, so that
+    /// it can be replayed.
+    pub fn capturing() -> Self {
+        RecordingMap {
+            capture: Some(Capture { key: K::clone, value: V::clone }),
+            ..Self::new()
+        }
+    }
+}
+
+impl<K, V> RecordingMap<K, V>
+    where K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        RecordingMap {
+            map: HashMap::new(),
+            trace: Trace { entries: Vec::new() },
+            capture: None,
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let op = Op::Insert { hash: make_hash(&key), key: self.capture_key(&key), value: self.capture_value(&value) };
+        let old = self.map.insert(key, value);
+        self.record(op);
+        old
+    }
+
+    pub fn replace(&mut self, key: K, value: V) -> Option<(K, V)> {
+        let op = Op::Replace { hash: make_hash(&key), key: self.capture_key(&key), value: self.capture_value(&value) };
+        let old = self.map.replace(key, value);
+        self.record(op);
+        old
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let old = self.map.remove(key);
+        self.record(Op::Remove { hash: make_hash(key), key: self.capture_key(key) });
+        old
+    }
+
+    /// Moves the entry for `key` into `dest`, recording a removal here and
+    /// an insert there. Unlike [`HashMap::transfer`] the key is rehashed,
+    /// since the two maps don't share a hasher.
+    pub fn transfer(&mut self, key: &K, dest: &mut RecordingMap<K, V>) -> Result<Option<V>, KeyNotFound> {
+        let index = self.map.find(key).ok_or(KeyNotFound)?;
+        let (_, (key, value)) = self.map.remove_index(index);
+        self.record(Op::Remove { hash: make_hash(&key), key: self.capture_key(&key) });
+        Ok(dest.insert(key, value))
+    }
+
+    /// Runs `f` on the entry for `key`, then records what it left there:
+    /// an insert of the value now under `key`, which `f` may have changed,
+    /// or a removal if `f` took it out. Nothing is recorded if the key was
+    /// absent and `f` left it that way.
+    pub fn entry<R>(&mut self, key: K, f: impl FnOnce(Entry<'_, K, V>) -> R) -> R {
+        let hash = make_hash(&key);
+        let captured = self.capture_key(&key);
+        let len = self.map.len();
+        let entry = self.map.entry(key);
+        let was_occupied = matches!(entry, Entry::Occupied(_));
+        let result = f(entry);
+        // `f` can only add or remove this one key.
+        let occupied = if was_occupied { self.map.len() == len } else { self.map.len() > len };
+        let op = match (was_occupied, occupied) {
+            (false, false) => return result,
+            (_, true) => {
+                let value = captured.as_ref().and_then(|key| self.map.get(key)).and_then(|value| self.capture_value(value));
+                Op::Insert { hash, key: captured, value }
+            }
+            (true, false) => Op::Remove { hash, key: captured },
+        };
+        self.record(op);
+        result
+    }
+
+    /// Keeps only the entries for which `f` returns true, recording the
+    /// keys it drops. Unlike [`HashMap::retain`], `f` can't change the
+    /// values it keeps, since those changes would go unrecorded.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let mut hashes = Vec::new();
+        let mut keys = self.capture.as_ref().map(|_| Vec::new());
+        let capture = &self.capture;
+        self.map.retain(|key, value| {
+            let keep = f(key, value);
+            if !keep {
+                hashes.push(make_hash(key));
+                if let (Some(keys), Some(capture)) = (&mut keys, capture) {
+                    keys.push((capture.key)(key));
+                }
+            }
+            keep
+        });
+        self.record(Op::Retain { hashes, keys });
+    }
+
+    pub fn clear(&mut self) {
+        self.map.drain();
+        self.record(Op::Clear);
+    }
+
+    /// Recorded as a [`clear`](RecordingMap::clear) up front, since the
+    /// map ends up empty however much of the iterator is used.
+    pub fn drain(&mut self) -> Drain<'_, K, V, SeededState> {
+        self.trace.entries.push(TraceEntry { op: Op::Clear, len: 0, buckets: self.map.table.slots() });
+        self.map.drain()
+    }
+
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        self.map.get(key)
+    }
+
+    pub fn trace(&self) -> &Trace<K, V> {
+        &self.trace
+    }
+
+    pub fn into_parts(self) -> (HashMap<K, V>, Trace<K, V>) {
+        (self.map, self.trace)
+    }
+
+    fn capture_key(&self, key: &K) -> Option<K> {
+        self.capture.as_ref().map(|capture| (capture.key)(key))
+    }
+
+    fn capture_value(&self, value: &V) -> Option<V> {
+        self.capture.as_ref().map(|capture| (capture.value)(value))
+    }
+
+    fn record(&mut self, op: Op<K, V>) {
+        let entry = TraceEntry { op, len: self.map.len(), buckets: self.map.table.slots() };
+        self.trace.entries.push(entry);
+    }
+}
+
+impl<K, V> Deref for RecordingMap<K, V> {
+    type Target = HashMap<K, V>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.map
+    }
+}
+
+/// Rebuilds the map a [`RecordingMap`] held by re-running its trace,
+/// checking after every step that the map has the recorded shape. The
+/// trace must come from a map made with [`RecordingMap::capturing`].
+pub fn replay<K, V>(trace: &Trace<K, V>) -> Result<HashMap<K, V>, ReplayError>
+    where K: Hash + Eq + Clone,
+          V: Clone,
+{
+    let mut map = HashMap::new();
+    for (step, recorded) in trace.entries.iter().enumerate() {
+        let hashes_match = match &recorded.op {
+            Op::Insert { hash, key: Some(key), value: Some(value) } => {
+                map.insert(key.clone(), value.clone());
+                make_hash(key) == *hash
+            }
+            Op::Replace { hash, key: Some(key), value: Some(value) } => {
+                map.replace(key.clone(), value.clone());
+                make_hash(key) == *hash
+            }
+            Op::Remove { hash, key: Some(key) } => {
+                map.remove(key);
+                make_hash(key) == *hash
+            }
+            Op::Retain { hashes, keys: Some(keys) } => {
+                for key in keys {
+                    map.remove(key);
+                }
+                keys.iter().map(make_hash).eq(hashes.iter().copied())
+            }
+            Op::Clear => {
+                map.drain();
+                true
+            }
+            _ => return Err(ReplayError::NotCaptured { step }),
+        };
+        if !hashes_match || (map.len(), map.table.slots()) != (recorded.len, recorded.buckets) {
+            return Err(ReplayError::Diverged { step });
+        }
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut map = RecordingMap::capturing();
+        for i in 0..20 {
+            map.insert(i, i.to_string());
+        }
+        map.remove(&3);
+        map.remove(&100);
+        map.replace(4, String::from("four"));
+        assert_eq!(map.get(&4).map(String::as_str), Some("four"));
+
+        let (original, trace) = map.into_parts();
+        assert_eq!(trace.entries.len(), 23);
+        assert_eq!(trace.entries[20].len, 19);
+
+        let rebuilt = replay(&trace).unwrap();
+        assert_eq!(rebuilt.len(), original.len());
+        for (k, v) in &original {
+            assert_eq!(rebuilt.get(k), Some(v));
+        }
+    }
+
+    #[test]
+    fn test_replay_every_mutation() {
+        let mut map = RecordingMap::capturing();
+        let mut other = RecordingMap::capturing();
+        for i in 0..30 {
+            map.insert(i, i * 10);
+        }
+        map.entry(1, |entry| *entry.or_insert(0) += 1);
+        map.entry(100, |entry| {
+            entry.or_insert(7);
+        });
+        map.entry(200, |entry| *entry.key());
+        assert_eq!(map.trace().entries.len(), 32);
+        map.entry(2, |entry| {
+            if let Entry::Occupied(entry) = entry {
+                entry.remove();
+            }
+        });
+        map.retain(|&k, _| k % 3 != 0);
+        assert_eq!(map.transfer(&4, &mut other), Ok(None));
+        assert_eq!(map.transfer(&4, &mut other), Err(KeyNotFound));
+
+        let (original, trace) = map.into_parts();
+        let rebuilt = replay(&trace).unwrap();
+        assert_eq!(rebuilt, original);
+        assert_eq!(rebuilt[&1], 11);
+        assert_eq!(rebuilt[&100], 7);
+        assert!(!rebuilt.contains_key(&2) && !rebuilt.contains_key(&4));
+        assert_eq!(replay(other.trace()).unwrap()[&4], 40);
+    }
+
+    #[test]
+    fn test_replay_clear_and_drain() {
+        let mut map = RecordingMap::capturing();
+        map.insert("a", 1);
+        map.clear();
+        map.insert("b", 2);
+        map.insert("c", 3);
+        assert_eq!(map.drain().take(1).count(), 1);
+        map.insert("d", 4);
+        assert_eq!(map.len(), 1);
+
+        let (original, trace) = map.into_parts();
+        assert_eq!(trace.entries[4].op, Op::Clear);
+        assert_eq!(replay(&trace).unwrap(), original);
+    }
+
+    #[test]
+    fn test_record_without_capture() {
+        // Neither `Clone` nor `Debug`.
+        #[derive(Hash, PartialEq, Eq)]
+        struct Key(u32);
+
+        let mut map = RecordingMap::new();
+        map.insert(Key(1), ());
+        map.insert(Key(2), ());
+        map.entry(Key(1), |entry| {
+            if let Entry::Occupied(entry) = entry {
+                entry.remove();
+            }
+        });
+        // Looking at a vacant entry and leaving it is not a mutation.
+        map.entry(Key(3), |entry| {
+            if let Entry::Vacant(entry) = entry {
+                entry.into_key();
+            }
+        });
+        map.retain(|_, _| false);
+
+        let trace = map.trace();
+        assert_eq!(trace.entries.len(), 4);
+        assert!(matches!(trace.entries[0].op, Op::Insert { key: None, value: None, hash } if hash == make_hash(&Key(1))));
+        assert!(matches!(trace.entries[2].op, Op::Remove { key: None, .. }));
+        assert!(matches!(&trace.entries[3].op, Op::Retain { hashes, keys: None } if hashes.len() == 1));
+        assert_eq!(trace.entries[3].len, 0);
+    }
+
+    #[test]
+    fn test_replay_needs_capture() {
+        let mut map = RecordingMap::new();
+        map.insert("a", 1);
+        assert_eq!(replay(map.trace()).err(), Some(ReplayError::NotCaptured { step: 0 }));
+    }
+
+    #[test]
+    fn test_replay_divergence() {
+        let mut map = RecordingMap::capturing();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        let (_, mut trace) = map.into_parts();
+        trace.entries[1].len = 7;
+        assert_eq!(replay(&trace).err(), Some(ReplayError::Diverged { step: 1 }));
+    }
+}
@@ -0,0 +1,103 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hasher};
+#[allow(deprecated)]
+use core::hash::SipHasher;
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+
+// Spreads the one seed over SipHash's two keys.
+const K1_MASK: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// The default `BuildHasher`: SipHash-2-4 keyed from a 64-bit seed.
+///
+/// [`new`](SeededState::new) picks a random seed, so like `RandomState` it
+/// resists keys chosen to collide. Unlike `RandomState` the seed can be
+/// read back with [`seed`](SeededState::seed) and restored with
+/// [`from_seed`](SeededState::from_seed), for a reloaded map to hash, and
+/// so place its entries, exactly as the one that was saved. Anyone who
+/// learns the seed can choose collisions, so keep it as private as the
+/// keys.
+///
+/// ```
+/// use core::hash::BuildHasher;
+/// use hashmap::SeededState;
+///
+/// let state = SeededState::new();
+/// let again = SeededState::from_seed(state.seed());
+/// assert_eq!(state.hash_one("key"), again.hash_one("key"));
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    /// A state with a random seed.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        SeededState::from_seed(RandomState::new().hash_one(0u64))
+    }
+
+    pub const fn from_seed(seed: u64) -> Self {
+        SeededState { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SeededState {
+    fn default() -> Self {
+        SeededState::new()
+    }
+}
+
+// Leaving the seed out keeps it out of logs.
+impl fmt::Debug for SeededState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeededState").finish_non_exhaustive()
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SeededHasher;
+
+    #[allow(deprecated)]
+    fn build_hasher(&self) -> SeededHasher {
+        SeededHasher(SipHasher::new_with_keys(self.seed, self.seed ^ K1_MASK))
+    }
+}
+
+/// The hasher [`SeededState`] builds.
+#[allow(deprecated)]
+#[derive(Debug, Clone)]
+pub struct SeededHasher(SipHasher);
+
+impl Hasher for SeededHasher {
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed() {
+        let state = SeededState::from_seed(7);
+        assert_eq!(state.seed(), 7);
+        assert_eq!(state.hash_one(42u64), SeededState::from_seed(7).hash_one(42u64));
+        assert_ne!(state.hash_one(42u64), SeededState::from_seed(8).hash_one(42u64));
+        assert_eq!(format!("{:?}", state), "SeededState { .. }");
+
+        // Fresh states are seeded independently.
+        assert_ne!(SeededState::new().seed(), SeededState::new().seed());
+    }
+}
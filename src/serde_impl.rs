@@ -0,0 +1,408 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+#[cfg(feature = "rayon")]
+use std::convert::Infallible;
+#[cfg(feature = "rayon")]
+use std::sync::RwLock;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Unexpected, Visitor};
+#[cfg(feature = "rayon")]
+use serde::ser::SerializeSeq;
+use serde::ser::{Serialize, Serializer};
+
+use crate::{AllocReason, HashMap, SeededState};
+#[cfg(feature = "rayon")]
+use crate::ShardedHashMap;
+
+// Upper bound on what a length hint from the input may preallocate, so a
+// malicious length prefix can't exhaust memory before any entry is read.
+const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+
+/// Serialized as a map, in iteration order.
+impl<K, V, S> Serialize for HashMap<K, V, S>
+    where K: Serialize,
+          V: Serialize,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+/// Deserialized from a map, sized up front from the input's length hint.
+/// A repeated key keeps its last value.
+impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
+    where K: Deserialize<'de> + Hash + Eq,
+          V: Deserialize<'de>,
+          S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor { hash_builder: S::default(), buckets: None, marker: PhantomData })
+    }
+}
+
+/// Serialized as its seed.
+impl Serialize for SeededState {
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.serialize_u64(self.seed())
+    }
+}
+
+impl<'de> Deserialize<'de> for SeededState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(SeededState::from_seed)
+    }
+}
+
+/// Serializes a map together with its hasher and bucket count, for
+/// `#[serde(with = "hashmap::with_hasher")]`.
+///
+/// Since the hasher comes back too, a [`SeededState`] with its seed, the
+/// reloaded map hashes every key as the saved one did and puts it back in
+/// the bucket it was saved from. That holds for maps with the default
+/// resize policy whose table fits under the same preallocation cap as
+/// plain deserializing; a bigger one is sized from its entries instead.
+///
+/// ```
+/// use hashmap::{with_hasher, HashMap};
+///
+/// let map: HashMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+/// let mut json = Vec::new();
+/// with_hasher::serialize(&map, &mut serde_json::Serializer::new(&mut json)).unwrap();
+///
+/// let back: HashMap<u32, u32> = with_hasher::deserialize(&mut serde_json::Deserializer::from_slice(&json)).unwrap();
+/// assert_eq!(back.hasher(), map.hasher());
+/// ```
+pub mod with_hasher {
+    use super::*;
+
+    pub fn serialize<K, V, S, T>(map: &HashMap<K, V, S>, serializer: T) -> Result<T::Ok, T::Error>
+        where K: Serialize,
+              V: Serialize,
+              S: Serialize,
+              T: Serializer,
+    {
+        (&map.hash_builder, map.table.slots(), map).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, K, V, S, D>(deserializer: D) -> Result<HashMap<K, V, S>, D::Error>
+        where K: Deserialize<'de> + Hash + Eq,
+              V: Deserialize<'de>,
+              S: Deserialize<'de> + BuildHasher,
+              D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(3, WithHasherVisitor(PhantomData))
+    }
+}
+
+struct WithHasherVisitor<K, V, S>(PhantomData<HashMap<K, V, S>>);
+
+impl<'de, K, V, S> Visitor<'de> for WithHasherVisitor<K, V, S>
+    where K: Deserialize<'de> + Hash + Eq,
+          V: Deserialize<'de>,
+          S: Deserialize<'de> + BuildHasher,
+{
+    type Value = HashMap<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a hasher, a bucket count and a map")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let hash_builder = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let buckets: usize = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        if buckets != 0 && !buckets.is_power_of_two() {
+            return Err(de::Error::invalid_value(Unexpected::Unsigned(buckets as u64), &"a power of two"));
+        }
+        let visitor = MapVisitor { hash_builder, buckets: Some(buckets), marker: PhantomData };
+        seq.next_element_seed(visitor)?.ok_or_else(|| de::Error::invalid_length(2, &self))
+    }
+}
+
+// Builds the map with `hash_builder`, sized to `buckets` if given and
+// under the cap, and otherwise from the input's length hint.
+struct MapVisitor<K, V, S> {
+    hash_builder: S,
+    buckets: Option<usize>,
+    marker: PhantomData<HashMap<K, V, S>>,
+}
+
+impl<'de, K, V, S> DeserializeSeed<'de> for MapVisitor<K, V, S>
+    where K: Deserialize<'de> + Hash + Eq,
+          V: Deserialize<'de>,
+          S: BuildHasher,
+{
+    type Value = HashMap<K, V, S>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+    where K: Deserialize<'de> + Hash + Eq,
+          V: Deserialize<'de>,
+          S: BuildHasher,
+{
+    type Value = HashMap<K, V, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let limit = MAX_PREALLOC_BYTES / mem::size_of::<(K, V)>().max(1);
+        let mut map = HashMap::with_hasher(self.hash_builder);
+        match self.buckets {
+            Some(buckets) if buckets <= limit => map.resize_to(buckets, AllocReason::Grow),
+            _ => map.reserve(access.size_hint().unwrap_or(0).min(limit)),
+        }
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+/// Serialized as a sequence of segments, one per shard, each a map like a
+/// [`HashMap`]'s. A `Serializer` takes one value at a time, so the shards
+/// are read-locked and written one after another;
+/// [`par_serialize_shards`](ShardedHashMap::par_serialize_shards) encodes
+/// them in parallel instead, into separate buffers.
+#[cfg(feature = "rayon")]
+impl<K, V, S> Serialize for ShardedHashMap<K, V, S>
+    where K: Serialize + Hash + Eq,
+          V: Serialize,
+          S: BuildHasher + Clone,
+{
+    fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.shard_count()))?;
+        for shard in self.shards() {
+            seq.serialize_element(&*shard)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserialized from a sequence of segments, with one shard per segment.
+/// The segments are read in order, then their entries are hashed and
+/// inserted into their shards in parallel, as by
+/// [`par_deserialize_shards`](ShardedHashMap::par_deserialize_shards).
+#[cfg(feature = "rayon")]
+impl<'de, K, V, S> Deserialize<'de> for ShardedHashMap<K, V, S>
+    where K: Deserialize<'de> + Hash + Eq + Send + Sync,
+          V: Deserialize<'de> + Send + Sync,
+          S: BuildHasher + Clone + Default + Send + Sync,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let segments = deserializer.deserialize_seq(SegmentsVisitor::<K, V, S>(PhantomData))?;
+        match ShardedHashMap::par_deserialize_shards(segments, S::default(), Ok::<_, Infallible>) {
+            Ok(map) => Ok(map),
+            Err(never) => match never {},
+        }
+    }
+}
+
+// Reads a sharded map's segments without hashing anything, refusing more
+// of them than the preallocation cap has room for as empty shards.
+#[cfg(feature = "rayon")]
+struct SegmentsVisitor<K, V, S>(PhantomData<ShardedHashMap<K, V, S>>);
+
+#[cfg(feature = "rayon")]
+impl<'de, K, V, S> Visitor<'de> for SegmentsVisitor<K, V, S>
+    where K: Deserialize<'de>,
+          V: Deserialize<'de>,
+{
+    type Value = Vec<Vec<(K, V)>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of maps")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let limit = MAX_PREALLOC_BYTES / mem::size_of::<RwLock<HashMap<K, V, S>>>();
+        let mut segments = Vec::new();
+        while let Some(segment) = seq.next_element_seed(SegmentVisitor(PhantomData))? {
+            if segments.len() == limit {
+                return Err(de::Error::invalid_length(limit + 1, &"no more segments than the shard limit"));
+            }
+            segments.push(segment);
+        }
+        Ok(segments)
+    }
+}
+
+#[cfg(feature = "rayon")]
+struct SegmentVisitor<K, V>(PhantomData<(K, V)>);
+
+#[cfg(feature = "rayon")]
+impl<'de, K, V> DeserializeSeed<'de> for SegmentVisitor<K, V>
+    where K: Deserialize<'de>,
+          V: Deserialize<'de>,
+{
+    type Value = Vec<(K, V)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'de, K, V> Visitor<'de> for SegmentVisitor<K, V>
+    where K: Deserialize<'de>,
+          V: Deserialize<'de>,
+{
+    type Value = Vec<(K, V)>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let limit = MAX_PREALLOC_BYTES / mem::size_of::<(K, V)>().max(1);
+        let mut entries = Vec::with_capacity(access.size_hint().unwrap_or(0).min(limit));
+        while let Some(entry) = access.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PortableState;
+    use crate::raw::RawTable;
+
+    #[test]
+    fn test_json_round_trip() {
+        let map: HashMap<u64, Vec<i32>> = (0..20).map(|i| (i * 1000, vec![i as i32; 3])).collect();
+        let json = serde_json::to_string(&map).unwrap();
+        let back: HashMap<u64, Vec<i32>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.len(), 20);
+        assert!(map.iter().all(|(k, v)| back.get(k) == Some(v)));
+
+        let parsed: HashMap<i8, bool, PortableState> = serde_json::from_str(r#"{"-1": true, "7": false, "7": true}"#).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.get(&7), Some(&true));
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let map: HashMap<(u8, char), Option<String>> = (0..100u8)
+            .map(|i| ((i, char::from(b'a' + i % 26)), Some(i.to_string()).filter(|_| i % 3 != 0)))
+            .collect();
+        let bytes = bincode::serialize(&map).unwrap();
+        let back: HashMap<(u8, char), Option<String>> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.len(), 100);
+        assert!(map.iter().all(|(k, v)| back.get(k) == Some(v)));
+        // Sized from the length prefix, so no resize after the first.
+        assert_eq!(back.capacity_report().resizes, 1);
+    }
+
+    // Every bucket's key, or `None` where the bucket is empty.
+    fn layout<T: Copy>(table: &RawTable<(T, T)>) -> Vec<Option<T>> {
+        (0..table.positions()).map(|index| table.is_occupied(index).then(|| table.get(index).0)).collect()
+    }
+
+    #[test]
+    fn test_with_hasher_round_trip() {
+        let mut map = HashMap::new();
+        for i in 0..300u32 {
+            map.insert(i, i);
+        }
+        // Leaves more buckets than the entries alone would be given.
+        map.retain(|&k, _| k % 3 == 0);
+
+        let json = serde_json::to_string(&Wrap(&map)).unwrap();
+        let Wrap(back): Wrap<HashMap<u32, u32>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.hasher().seed(), map.hasher().seed());
+        assert_eq!(back.table.slots(), map.table.slots());
+        assert_eq!(layout(&back.table), layout(&map.table));
+        assert_eq!(back.capacity_report().resizes, 1);
+
+        let bytes = bincode::serialize(&Wrap(&map)).unwrap();
+        let Wrap(back): Wrap<HashMap<u32, u32>> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(layout(&back.table), layout(&map.table));
+
+        // The plain format reseeds, and only sizes for the entries.
+        let plain: HashMap<u32, u32> = serde_json::from_str(&serde_json::to_string(&map).unwrap()).unwrap();
+        assert_ne!(plain.hasher().seed(), map.hasher().seed());
+        assert!(plain.table.slots() < map.table.slots());
+
+        let seeded: HashMap<u8, u8, SeededState> = serde_json::from_str("{\"1\": 2}").unwrap();
+        assert_eq!(seeded.get(&1), Some(&2));
+        assert!(serde_json::from_str::<Wrap<HashMap<u32, u32>>>("[7, 3, {}]").is_err());
+    }
+
+    // Serializes with or deserializes from `with_hasher`.
+    struct Wrap<M>(M);
+
+    impl<K: Serialize, V: Serialize> Serialize for Wrap<&HashMap<K, V>> {
+        fn serialize<T: Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+            with_hasher::serialize(self.0, serializer)
+        }
+    }
+
+    impl<'de, K, V> Deserialize<'de> for Wrap<HashMap<K, V>>
+        where K: Deserialize<'de> + Hash + Eq,
+              V: Deserialize<'de>,
+    {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            with_hasher::deserialize(deserializer).map(Wrap)
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_sharded_round_trip() {
+        let map = ShardedHashMap::with_shards(8);
+        for i in 0..500u32 {
+            map.insert(i, i.to_string());
+        }
+        let same = |back: &ShardedHashMap<u32, String>| {
+            back.len() == 500 && map.iter().all(|entry| back.get(entry.key()).as_deref() == Some(&*entry))
+        };
+
+        let json = serde_json::to_string(&map).unwrap();
+        let back: ShardedHashMap<u32, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.shard_count(), 8);
+        assert!(same(&back));
+
+        let bytes = bincode::serialize(&map).unwrap();
+        let back: ShardedHashMap<u32, String> = bincode::deserialize(&bytes).unwrap();
+        assert!(same(&back));
+
+        // A key in two segments keeps the value from the later one.
+        let back: ShardedHashMap<u32, u32> = serde_json::from_str(r#"[{"1": 10, "2": 20}, {}, {"1": 11}]"#).unwrap();
+        assert_eq!((back.shard_count(), back.len()), (4, 2));
+        assert_eq!(*back.get(&1).unwrap(), 11);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_serialize_shards() {
+        let map = ShardedHashMap::with_shards(8);
+        for i in 0..500u32 {
+            map.insert(i, vec![i; 2]);
+        }
+        let segments = map.par_serialize_shards(bincode::serialize).unwrap();
+        assert_eq!(segments.len(), 8);
+
+        // Each segment stands alone, so it can be read back on its own.
+        let first: HashMap<u32, Vec<u32>, PortableState> = bincode::deserialize(&segments[0]).unwrap();
+        assert_eq!(first.len(), map.shards().next().unwrap().len());
+
+        let back: ShardedHashMap<u32, Vec<u32>> = ShardedHashMap::par_deserialize_shards(segments, SeededState::new(), |bytes| {
+            bincode::deserialize::<HashMap<u32, Vec<u32>, PortableState>>(&bytes)
+        })
+        .unwrap();
+        assert_eq!(back.len(), 500);
+        assert!((0..500).all(|i| back.get(&i).as_deref() == Some(&vec![i; 2])));
+
+        let bad = vec![bincode::serialize(&map.into_shards()[0]).unwrap(), vec![0xff]];
+        let decode = |bytes: Vec<u8>| bincode::deserialize::<HashMap<u32, Vec<u32>, PortableState>>(&bytes);
+        assert!(ShardedHashMap::<u32, Vec<u32>>::par_deserialize_shards(bad, SeededState::new(), decode).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::ops::{BitAnd, BitOr, BitXor, Sub};
+use crate::key_set::{Difference, Intersection, KeyFilter, SymmetricDifference, Union};
+use crate::{iter, DefaultHashBuilder, Equivalent, HashMap, KeySet, Keys};
+#[cfg(feature = "std")]
+use crate::SeededState;
+
+/// Hash set: a [`HashMap`] with `()` values, so it hashes, grows and
+/// allocates exactly as the map does.
+///
+/// The set operations are those of [`KeySet`], yielding references into
+/// the two sets as they go; the `|`, `&`, `-` and `^` operators on `&HashSet`
+/// collect them into a new set instead.
+///
+/// ```
+/// use hashmap::HashSet;
+///
+/// let a: HashSet<u32> = (0..5).collect();
+/// let b: HashSet<u32> = (3..8).collect();
+///
+/// let mut common: Vec<_> = a.intersection(&b).copied().collect();
+/// common.sort();
+/// assert_eq!(common, [3, 4]);
+/// assert_eq!(a.union(&b).count(), 8);
+/// ```
+pub struct HashSet<T, S = DefaultHashBuilder> {
+    map: HashMap<T, (), S>,
+}
+
+/// Iterator returned by [`HashSet::iter`].
+pub type Iter<'a, T> = Keys<'a, T, ()>;
+
+impl<T, S> Default for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> HashSet<T, SeededState>
+    where T: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self::with_hasher(SeededState::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, SeededState::new())
+    }
+}
+
+impl<T, S> HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashSet { map: HashMap::with_hasher(hash_builder) }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashSet { map: HashMap::with_capacity_and_hasher(capacity, hash_builder) }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Adds `value`, returning false if the set already held an equal one.
+    /// The value already in the set is kept.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    pub fn contains<Q: Hash + Equivalent<T> + ?Sized>(&self, value: &Q) -> bool {
+        self.map.contains_key(value)
+    }
+
+    /// Removes `value`, returning whether it was in the set.
+    pub fn remove<Q: Hash + Equivalent<T> + ?Sized>(&mut self, value: &Q) -> bool {
+        self.map.remove(value).is_some()
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut keep: F) {
+        self.map.retain(|value, _| keep(value));
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    /// The set's values as a [`KeySet`], for operations against a map's keys.
+    pub fn as_key_set(&self) -> KeySet<'_, T, (), S> {
+        self.map.key_set()
+    }
+
+    /// Values in either set, each yielded once.
+    pub fn union<'a>(&'a self, other: &'a HashSet<T, S>) -> Union<'a, T, (), (), S> {
+        self.as_key_set().union(other.as_key_set())
+    }
+
+    /// Values in both sets.
+    pub fn intersection<'a>(&'a self, other: &'a HashSet<T, S>) -> Intersection<'a, T, (), (), S> {
+        self.as_key_set().intersection(other.as_key_set())
+    }
+
+    /// Values in this set but not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a HashSet<T, S>) -> Difference<'a, T, (), (), S> {
+        self.as_key_set().difference(other.as_key_set())
+    }
+
+    /// Values in exactly one of the two sets.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a HashSet<T, S>) -> SymmetricDifference<'a, T, (), (), S> {
+        self.as_key_set().symmetric_difference(other.as_key_set())
+    }
+
+    /// Whether every value of this set is in `other`. A larger set is
+    /// ruled out by its length, so only the smaller set is ever iterated.
+    pub fn is_subset(&self, other: &HashSet<T, S>) -> bool {
+        self.map.keys_subset_of(&other.map)
+    }
+
+    pub fn is_superset(&self, other: &HashSet<T, S>) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Whether the sets have no value in common. Iterates the smaller set
+    /// and probes the larger one.
+    pub fn is_disjoint(&self, other: &HashSet<T, S>) -> bool {
+        self.map.keys_disjoint_with(&other.map)
+    }
+}
+
+impl<T, S> HashSet<T, S> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        self.map.keys()
+    }
+}
+
+impl<T, S> KeyFilter<T> for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher,
+{
+    fn key_count(&self) -> usize {
+        self.len()
+    }
+
+    fn contains_key(&self, value: &T) -> bool {
+        self.contains(value)
+    }
+
+    fn for_each_key(&self, f: &mut dyn FnMut(&T)) {
+        self.iter().for_each(f);
+    }
+}
+
+impl<T, S> Extend<T> for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|value| (value, ())));
+    }
+}
+
+impl<T, S> FromIterator<T> for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        HashSet { map: iter.into_iter().map(|value| (value, ())).collect() }
+    }
+}
+
+impl<T, S> fmt::Debug for HashSet<T, S>
+    where T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T, S> Clone for HashSet<T, S>
+    where T: Clone,
+          S: Clone,
+{
+    fn clone(&self) -> Self {
+        HashSet { map: self.map.clone() }
+    }
+}
+
+impl<T, S> PartialEq for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.map.same_keys(&other.map)
+    }
+}
+
+impl<T, S> Eq for HashSet<T, S>
+    where T: Hash + Eq,
+          S: BuildHasher,
+{}
+
+/// `&a | &b` collects the union of two sets into a new one.
+impl<T, S> BitOr<&HashSet<T, S>> for &HashSet<T, S>
+    where T: Hash + Eq + Clone,
+          S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitor(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        self.union(other).cloned().collect()
+    }
+}
+
+/// `&a & &b` collects the intersection of two sets into a new one.
+impl<T, S> BitAnd<&HashSet<T, S>> for &HashSet<T, S>
+    where T: Hash + Eq + Clone,
+          S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitand(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        self.intersection(other).cloned().collect()
+    }
+}
+
+/// `&a - &b` collects the values of `a` not in `b` into a new set.
+impl<T, S> Sub<&HashSet<T, S>> for &HashSet<T, S>
+    where T: Hash + Eq + Clone,
+          S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn sub(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        self.difference(other).cloned().collect()
+    }
+}
+
+/// `&a ^ &b` collects the symmetric difference of two sets into a new one.
+impl<T, S> BitXor<&HashSet<T, S>> for &HashSet<T, S>
+    where T: Hash + Eq + Clone,
+          S: BuildHasher + Default,
+{
+    type Output = HashSet<T, S>;
+
+    fn bitxor(self, other: &HashSet<T, S>) -> HashSet<T, S> {
+        self.symmetric_difference(other).cloned().collect()
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a HashSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { inner: self.map.into_iter() }
+    }
+}
+
+/// Iterator returned by `HashSet::into_iter`.
+pub struct IntoIter<T, S> {
+    inner: iter::IntoIter<T, (), S>,
+}
+
+impl<T, S> Iterator for IntoIter<T, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.inner.next().map(|(value, ())| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T, S> ExactSizeIterator for IntoIter<T, S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted<'a>(values: impl Iterator<Item = &'a u32>) -> Vec<u32> {
+        let mut values: Vec<u32> = values.copied().collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_insert_contains_remove() {
+        let mut set = HashSet::new();
+        assert!(set.insert(String::from("a")));
+        assert!(!set.insert(String::from("a")));
+        set.extend((0..50).map(|i| i.to_string()));
+        assert_eq!(set.len(), 51);
+        assert!(set.contains("a") && set.contains("49"));
+
+        assert!(set.remove("a"));
+        assert!(!set.remove("a"));
+        set.retain(|s| s.len() == 1);
+        assert_eq!(set.len(), 10);
+        assert_eq!(format!("{:?}", set.iter().filter(|s| *s == "7").collect::<HashSet<_>>()), r#"{"7"}"#);
+
+        let mut values: Vec<String> = set.clone().into_iter().collect();
+        values.sort();
+        assert_eq!(values, (0..10).map(|i| i.to_string()).collect::<Vec<_>>());
+        assert_eq!(set, values.into_iter().rev().collect());
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let a: HashSet<u32> = (0..6).collect();
+        let b: HashSet<u32> = (4..9).collect();
+
+        assert_eq!(sorted(a.union(&b)), (0..9).collect::<Vec<_>>());
+        assert_eq!(sorted(a.intersection(&b)), [4, 5]);
+        assert_eq!(sorted(a.difference(&b)), [0, 1, 2, 3]);
+        assert_eq!(sorted(a.symmetric_difference(&b)), [0, 1, 2, 3, 6, 7, 8]);
+        assert!(!a.is_disjoint(&b));
+
+        let small: HashSet<u32> = (1..3).collect();
+        assert!(small.is_subset(&a) && a.is_superset(&small));
+        assert!(!small.is_subset(&b));
+
+        assert_eq!(sorted((&a | &b).iter()), (0..9).collect::<Vec<_>>());
+        assert_eq!(sorted((&a & &b).iter()), [4, 5]);
+        assert_eq!(sorted((&a - &b).iter()), [0, 1, 2, 3]);
+        assert_eq!(&a ^ &b, (0..4).chain(6..9).collect());
+        assert!((&small & &b).is_empty());
+
+        // Also works against a map's keys.
+        let map: HashMap<u32, &str> = [(1, "one"), (7, "seven")].iter().copied().collect();
+        assert_eq!(sorted(small.as_key_set().difference(map.key_set())), [2]);
+    }
+
+    #[test]
+    fn test_relations() {
+        let empty: HashSet<u32> = HashSet::new();
+        let a: HashSet<u32> = (0..10).collect();
+        let same: HashSet<u32> = (0..10).rev().collect();
+        let part: HashSet<u32> = [2, 3, 5].iter().copied().collect();
+        let apart: HashSet<u32> = (20..25).collect();
+        let overlap: HashSet<u32> = (8..12).collect();
+
+        // The empty set is a subset of everything, and disjoint with it.
+        assert!(empty.is_subset(&empty) && empty.is_superset(&empty) && empty.is_disjoint(&empty));
+        assert!(empty.is_subset(&a) && a.is_superset(&empty));
+        assert!(!a.is_subset(&empty) && !empty.is_superset(&a));
+        assert!(empty.is_disjoint(&a) && a.is_disjoint(&empty));
+
+        // Equal sets contain each other.
+        assert!(a.is_subset(&same) && same.is_subset(&a));
+        assert!(a.is_superset(&same) && !a.is_disjoint(&same));
+
+        assert!(part.is_subset(&a) && !a.is_subset(&part));
+        assert!(a.is_superset(&part) && !part.is_superset(&a));
+        assert!(apart.is_disjoint(&a) && a.is_disjoint(&apart));
+        assert!(!overlap.is_disjoint(&a) && !a.is_disjoint(&overlap));
+        assert!(!overlap.is_subset(&a) && !a.is_superset(&overlap));
+    }
+}
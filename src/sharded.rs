@@ -0,0 +1,780 @@
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(feature = "rayon")]
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+
+use crate::{DefaultHashBuilder, Equivalent, HashMap, SeededState};
+
+/// Concurrent map split into independently locked shards, so writers to
+/// different shards don't contend.
+///
+/// A key's hash picks its shard, and every shard's [`HashMap`] hashes with a
+/// clone of the same hasher, so each operation hashes the key once and
+/// takes one lock. No method holds two shard locks at once, though the
+/// entries [`iter`] yields keep their shards' locks while they're alive.
+///
+/// ```
+/// use hashmap::ShardedHashMap;
+///
+/// let map = ShardedHashMap::new();
+/// std::thread::scope(|scope| {
+///     for t in 0..4 {
+///         let map = &map;
+///         scope.spawn(move || {
+///             for i in 0..100 {
+///                 map.insert(t * 100 + i, i);
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(map.len(), 400);
+/// assert_eq!(*map.get(&250).unwrap(), 50);
+/// ```
+///
+/// [`iter`]: ShardedHashMap::iter
+pub struct ShardedHashMap<K, V, S = DefaultHashBuilder> {
+    shards: Box<[RwLock<HashMap<K, V, S>>]>,
+    hash_builder: S,
+}
+
+impl<K, V> Default for ShardedHashMap<K, V, SeededState>
+    where K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> ShardedHashMap<K, V, SeededState>
+    where K: Hash + Eq,
+{
+    /// Creates a map with four shards per available CPU.
+    pub fn new() -> Self {
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(4 * cpus)
+    }
+
+    /// Creates a map with `shards` shards, rounded up to a power of two.
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, SeededState::new())
+    }
+}
+
+impl<K, V, S> ShardedHashMap<K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher + Clone,
+{
+    pub fn with_shards_and_hasher(shards: usize, hash_builder: S) -> Self {
+        assert!(shards > 0, "ShardedHashMap needs at least one shard");
+        let shards = (0..shards.next_power_of_two())
+            .map(|_| RwLock::new(HashMap::with_hasher(hash_builder.clone())))
+            .collect();
+        ShardedHashMap { shards, hash_builder }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Each shard's size, read-locking one shard at a time, for spotting
+    /// keys that pile up in a few shards.
+    pub fn shard_stats(&self) -> Vec<ShardStats> {
+        self.shards()
+            .map(|shard| ShardStats { len: shard.len(), capacity: shard.capacity(), buckets: shard.table.slots() })
+            .collect()
+    }
+
+    /// Rebuilds the map with `shards` shards, rounded up to a power of two,
+    /// moving every entry across without hashing its key again. Each new
+    /// shard starts with room for an even share of the entries.
+    ///
+    /// Panics if `shards` is zero.
+    pub fn reshard(&mut self, shards: usize) {
+        assert!(shards > 0, "ShardedHashMap needs at least one shard");
+        let count = shards.next_power_of_two();
+        let share = self.len() / count;
+        let fresh = (0..count)
+            .map(|_| RwLock::new(HashMap::with_capacity_and_hasher(share, self.hash_builder.clone())))
+            .collect();
+        for shard in mem::replace(&mut self.shards, fresh).into_vec() {
+            let mut shard = into_inner(shard);
+            let mut cursor = 0;
+            while let Some((hash, (key, value))) = shard.table.pop_from(&mut cursor) {
+                let index = self.shard_index(hash);
+                let dest = get_mut(&mut self.shards[index]);
+                dest.prepare_insert();
+                dest.push_new(hash, key, value);
+            }
+        }
+    }
+
+    /// Total entries, counted one shard at a time; concurrent writers can
+    /// make this differ from every state the map was actually in.
+    pub fn len(&self) -> usize {
+        self.shards().map(|shard| shard.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards().all(|shard| shard.is_empty())
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        self.write(hash).insert_hashed(hash, key, value)
+    }
+
+    /// Returns the value under a read lock on its shard, which is held
+    /// until the guard is dropped. Don't touch the map from the same thread
+    /// while the guard is alive, even to read: a second read lock on the
+    /// shard can deadlock with a writer waiting for it.
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<ValueRef<'_, K, V, S>> {
+        let hash = self.hash_builder.hash_one(key);
+        let shard = self.read(hash);
+        let index = shard.find_hashed(hash, key)?;
+        Some(ValueRef { shard, index })
+    }
+
+    /// Like `get`, under a write lock.
+    pub fn get_mut<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<ValueRefMut<'_, K, V, S>> {
+        let hash = self.hash_builder.hash_one(key);
+        let shard = self.write(hash);
+        let index = shard.find_hashed(hash, key)?;
+        Some(ValueRefMut { shard, index })
+    }
+
+    /// Looks up every key in `keys`, read-locking each shard once however
+    /// many of the keys it holds, and returns clones of the values in the
+    /// order of `keys`. The shards are still locked one at a time, so the
+    /// results can mix states of the map that never existed together.
+    pub fn bulk_get<Q>(&self, keys: &[&Q]) -> Vec<Option<V>>
+        where Q: Hash + Equivalent<K> + ?Sized,
+              V: Clone,
+    {
+        let mut order: Vec<(usize, u64, usize)> = keys.iter().enumerate()
+            .map(|(i, key)| {
+                let hash = self.hash_builder.hash_one(key);
+                (self.shard_index(hash), hash, i)
+            })
+            .collect();
+        order.sort_unstable_by_key(|&(shard, _, _)| shard);
+        let mut values = vec![None; keys.len()];
+        for group in order.chunk_by(|a, b| a.0 == b.0) {
+            let shard = read(&self.shards[group[0].0]);
+            for &(_, hash, i) in group {
+                values[i] = shard.find_hashed(hash, keys[i]).map(|index| shard.table.get(index).1.clone());
+            }
+        }
+        values
+    }
+
+    /// Write-locks the key's shard and looks the key up once, for a
+    /// check-then-insert that no other thread can get in the middle of.
+    /// The lock is held until the entry, or the [`ValueRefMut`] it turns
+    /// into, is dropped:
+    ///
+    /// ```
+    /// let counts = hashmap::ShardedHashMap::new();
+    /// for word in "a b a".split(' ') {
+    ///     *counts.entry(word).or_insert(0) += 1;
+    /// }
+    /// assert_eq!(*counts.get("a").unwrap(), 2);
+    /// ```
+    pub fn entry(&self, key: K) -> ShardedEntry<'_, K, V, S> {
+        let hash = self.hash_builder.hash_one(&key);
+        let shard = self.write(hash);
+        match shard.find_hashed(hash, &key) {
+            Some(index) => ShardedEntry::Occupied(ShardedOccupiedEntry { shard, index }),
+            None => ShardedEntry::Vacant(ShardedVacantEntry { shard, key, hash }),
+        }
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        let hash = self.hash_builder.hash_one(key);
+        self.read(hash).find_hashed(hash, key).is_some()
+    }
+
+    pub fn remove<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<V> {
+        let hash = self.hash_builder.hash_one(key);
+        let mut shard = self.write(hash);
+        let index = shard.find_hashed(hash, key)?;
+        let (_, (_, value)) = shard.remove_index(index);
+        Some(value)
+    }
+
+    /// Yields every entry, read-locking one shard at a time. The lock on a
+    /// shard is shared by the iterator and every [`SharedRef`] it yields
+    /// from that shard, and is released once all of them have moved on or
+    /// been dropped. Until then writers to the shard wait, and as with
+    /// [`get`](ShardedHashMap::get), locking that shard again from the same
+    /// thread, even to read, can deadlock.
+    pub fn iter(&self) -> ShardedIter<'_, K, V, S> {
+        ShardedIter { shards: self.shards.iter(), shard: None, index: 0 }
+    }
+
+    /// Read-locks the shards one after another, for iterating the whole map
+    /// without blocking writers to the other shards. Each shard is locked
+    /// as it is yielded; drop its guard before taking the next to keep it
+    /// to one lock at a time.
+    pub fn shards(&self) -> Shards<'_, K, V, S> {
+        Shards { shards: self.shards.iter() }
+    }
+
+    pub fn into_shards(self) -> Vec<HashMap<K, V, S>> {
+        self.shards.into_vec().into_iter().map(into_inner).collect()
+    }
+
+    // The table uses the low bits of the hash, so pick the shard from the
+    // high ones.
+    fn shard_index(&self, hash: u64) -> usize {
+        (hash >> 32) as usize & (self.shards.len() - 1)
+    }
+
+    fn shard(&self, hash: u64) -> &RwLock<HashMap<K, V, S>> {
+        &self.shards[self.shard_index(hash)]
+    }
+
+    fn read(&self, hash: u64) -> RwLockReadGuard<'_, HashMap<K, V, S>> {
+        read(self.shard(hash))
+    }
+
+    fn write(&self, hash: u64) -> RwLockWriteGuard<'_, HashMap<K, V, S>> {
+        self.shard(hash).write().expect("ShardedHashMap shard poisoned")
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, S> ShardedHashMap<K, V, S>
+    where K: Hash + Eq + Send + Sync,
+          V: Send + Sync,
+          S: BuildHasher + Clone + Send + Sync,
+{
+    /// Encodes every shard with `encode` in parallel, one segment per
+    /// shard. Each shard is read-locked only while its own segment is
+    /// encoded, and the segments are independent of each other, for
+    /// [`par_deserialize_shards`] to decode concurrently:
+    ///
+    /// ```
+    /// use hashmap::{SeededState, ShardedHashMap};
+    ///
+    /// let map = ShardedHashMap::with_shards(4);
+    /// for i in 0..100u32 {
+    ///     map.insert(i, i * 2);
+    /// }
+    /// let segments = map.par_serialize_shards(|shard| Ok::<_, ()>(shard.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>()));
+    /// let back = ShardedHashMap::par_deserialize_shards(segments.unwrap(), SeededState::new(), Ok::<_, ()>).unwrap();
+    /// assert_eq!(*back.get(&7).unwrap(), 14);
+    /// ```
+    ///
+    /// [`par_deserialize_shards`]: ShardedHashMap::par_deserialize_shards
+    pub fn par_serialize_shards<B, E, F>(&self, encode: F) -> Result<Vec<B>, E>
+        where B: Send,
+              E: Send,
+              F: Fn(&HashMap<K, V, S>) -> Result<B, E> + Sync,
+    {
+        self.shards.par_iter().map(|shard| encode(&read(shard))).collect()
+    }
+
+    /// Builds a map with one shard per segment, rounded up to a power of
+    /// two. The segments are decoded and their entries hashed in parallel,
+    /// then each shard inserts the entries routed to it on its own worker,
+    /// without hashing them again. A key in more than one segment keeps the
+    /// value from the last.
+    pub fn par_deserialize_shards<B, I, E, F>(segments: Vec<B>, hash_builder: S, decode: F) -> Result<Self, E>
+        where B: Send,
+              I: IntoIterator<Item = (K, V)>,
+              E: Send,
+              F: Fn(B) -> Result<I, E> + Sync,
+    {
+        let mut map = Self::with_shards_and_hasher(segments.len().max(1), hash_builder);
+        let routed = segments
+            .into_par_iter()
+            .map(|segment| decode(segment).map(|entries| map.route(entries)))
+            .collect::<Result<Vec<_>, E>>()?;
+
+        let mut by_shard: Vec<Vec<Vec<(u64, K, V)>>> = (0..map.shards.len()).map(|_| Vec::new()).collect();
+        for segment in routed {
+            for (batches, entries) in by_shard.iter_mut().zip(segment) {
+                batches.push(entries);
+            }
+        }
+        map.shards.par_iter_mut().zip(by_shard).for_each(|(shard, batches)| {
+            let shard = get_mut(shard);
+            shard.reserve(batches.iter().map(Vec::len).sum());
+            for (hash, key, value) in batches.into_iter().flatten() {
+                shard.insert_hashed(hash, key, value);
+            }
+        });
+        Ok(map)
+    }
+
+    // Hashes `entries` and groups them by the shard each belongs in.
+    fn route(&self, entries: impl IntoIterator<Item = (K, V)>) -> Vec<Vec<(u64, K, V)>> {
+        let mut routed: Vec<Vec<_>> = (0..self.shards.len()).map(|_| Vec::new()).collect();
+        for (key, value) in entries {
+            let hash = self.hash_builder.hash_one(&key);
+            routed[self.shard_index(hash)].push((hash, key, value));
+        }
+        routed
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().expect("ShardedHashMap shard poisoned")
+}
+
+fn get_mut<T>(lock: &mut RwLock<T>) -> &mut T {
+    lock.get_mut().expect("ShardedHashMap shard poisoned")
+}
+
+fn into_inner<T>(lock: RwLock<T>) -> T {
+    lock.into_inner().expect("ShardedHashMap shard poisoned")
+}
+
+/// One shard's size, from [`ShardedHashMap::shard_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardStats {
+    pub len: usize,
+    /// How many entries the shard holds before its next resize.
+    pub capacity: usize,
+    pub buckets: usize,
+}
+
+impl ShardStats {
+    /// The fraction of the shard's buckets in use.
+    pub fn occupancy(&self) -> f64 {
+        match self.buckets {
+            0 => 0.0,
+            buckets => self.len as f64 / buckets as f64,
+        }
+    }
+}
+
+/// Value returned by [`ShardedHashMap::get`], read-locking its shard.
+pub struct ValueRef<'a, K, V, S = DefaultHashBuilder> {
+    shard: RwLockReadGuard<'a, HashMap<K, V, S>>,
+    index: usize,
+}
+
+impl<K, V, S> ValueRef<'_, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.shard.table.get(self.index).0
+    }
+}
+
+impl<K, V, S> Deref for ValueRef<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.shard.table.get(self.index).1
+    }
+}
+
+/// Value returned by [`ShardedHashMap::get_mut`], write-locking its shard.
+pub struct ValueRefMut<'a, K, V, S = DefaultHashBuilder> {
+    shard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    index: usize,
+}
+
+impl<'a, K, V, S> ValueRefMut<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.shard.table.get(self.index).0
+    }
+
+    /// Trades the write lock for a read lock on the same shard, with no
+    /// other writer getting in between.
+    pub fn downgrade(self) -> ValueRef<'a, K, V, S> {
+        ValueRef { shard: RwLockWriteGuard::downgrade(self.shard), index: self.index }
+    }
+}
+
+impl<K, V, S> Deref for ValueRefMut<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.shard.table.get(self.index).1
+    }
+}
+
+impl<K, V, S> DerefMut for ValueRefMut<'_, K, V, S> {
+    fn deref_mut(&mut self) -> &mut V {
+        &mut self.shard.table.get_mut(self.index).1
+    }
+}
+
+/// A view into one key's place in a [`ShardedHashMap`], returned by
+/// [`ShardedHashMap::entry`], write-locking its shard.
+pub enum ShardedEntry<'a, K, V, S = DefaultHashBuilder> {
+    Occupied(ShardedOccupiedEntry<'a, K, V, S>),
+    Vacant(ShardedVacantEntry<'a, K, V, S>),
+}
+
+pub struct ShardedOccupiedEntry<'a, K, V, S = DefaultHashBuilder> {
+    shard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    index: usize,
+}
+
+pub struct ShardedVacantEntry<'a, K, V, S = DefaultHashBuilder> {
+    shard: RwLockWriteGuard<'a, HashMap<K, V, S>>,
+    key: K,
+    hash: u64,
+}
+
+impl<'a, K, V, S> ShardedEntry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            ShardedEntry::Occupied(entry) => entry.key(),
+            ShardedEntry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> ValueRefMut<'a, K, V, S> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> ValueRefMut<'a, K, V, S> {
+        self.or_insert_with_key(|_| default())
+    }
+
+    /// Like `or_insert_with`, but `default` gets to see the key.
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> ValueRefMut<'a, K, V, S> {
+        match self {
+            ShardedEntry::Occupied(entry) => entry.into_ref(),
+            ShardedEntry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> ValueRefMut<'a, K, V, S>
+        where V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Runs `f` on the value if the key is present.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            ShardedEntry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                ShardedEntry::Occupied(entry)
+            }
+            vacant => vacant,
+        }
+    }
+}
+
+impl<'a, K, V, S> ShardedOccupiedEntry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.shard.table.get(self.index).0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.shard.table.get(self.index).1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.shard.table.get_mut(self.index).1
+    }
+
+    /// Turns the entry into a guard on its value, keeping the lock.
+    pub fn into_ref(self) -> ValueRefMut<'a, K, V, S> {
+        ValueRefMut { shard: self.shard, index: self.index }
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(mut self) -> (K, V) {
+        self.shard.remove_index(self.index).1
+    }
+}
+
+impl<'a, K, V, S> ShardedVacantEntry<'a, K, V, S>
+    where K: Hash + Eq,
+          S: BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value without hashing the key again, keeping the lock
+    /// for the returned guard.
+    pub fn insert(self, value: V) -> ValueRefMut<'a, K, V, S> {
+        let ShardedVacantEntry { mut shard, key, hash } = self;
+        shard.prepare_insert();
+        let index = shard.push_new(hash, key, value);
+        ValueRefMut { shard, index }
+    }
+}
+
+/// Iterator returned by [`ShardedHashMap::iter`].
+pub struct ShardedIter<'a, K, V, S = DefaultHashBuilder> {
+    shards: slice::Iter<'a, RwLock<HashMap<K, V, S>>>,
+    shard: Option<Arc<RwLockReadGuard<'a, HashMap<K, V, S>>>>,
+    index: usize,
+}
+
+impl<'a, K, V, S> Iterator for ShardedIter<'a, K, V, S> {
+    type Item = SharedRef<'a, K, V, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(shard) = &self.shard {
+                while self.index < shard.table.positions() {
+                    let index = self.index;
+                    self.index += 1;
+                    if shard.table.is_occupied(index) {
+                        return Some(SharedRef { shard: Arc::clone(shard), index });
+                    }
+                }
+            }
+            // Let go of this shard before locking the next.
+            self.shard = None;
+            self.shard = Some(Arc::new(read(self.shards.next()?)));
+            self.index = 0;
+        }
+    }
+}
+
+/// Entry yielded by [`ShardedHashMap::iter`], sharing a read lock on its
+/// shard with the other entries from it.
+pub struct SharedRef<'a, K, V, S = DefaultHashBuilder> {
+    shard: Arc<RwLockReadGuard<'a, HashMap<K, V, S>>>,
+    index: usize,
+}
+
+impl<K, V, S> SharedRef<'_, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.shard.table.get(self.index).0
+    }
+}
+
+impl<K, V, S> Deref for SharedRef<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.shard.table.get(self.index).1
+    }
+}
+
+/// Iterator returned by [`ShardedHashMap::shards`].
+pub struct Shards<'a, K, V, S = DefaultHashBuilder> {
+    shards: slice::Iter<'a, RwLock<HashMap<K, V, S>>>,
+}
+
+impl<'a, K, V, S> Iterator for Shards<'a, K, V, S> {
+    type Item = RwLockReadGuard<'a, HashMap<K, V, S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.shards.next().map(read)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.shards.size_hint()
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Shards<'a, K, V, S> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn test_concurrent_writers() {
+        let map = ShardedHashMap::with_shards(6);
+        assert_eq!(map.shard_count(), 8);
+        std::thread::scope(|scope| {
+            for t in 0..8u32 {
+                let map = &map;
+                scope.spawn(move || {
+                    for i in 0..1000 {
+                        map.insert(t * 1000 + i, i);
+                        if i % 2 == 1 {
+                            assert_eq!(map.remove(&(t * 1000 + i)), Some(i));
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 4000);
+        assert!(map.contains_key(&6998));
+        assert!(!map.contains_key(&6999));
+
+        let per_shard: Vec<usize> = map.shards().map(|shard| shard.len()).collect();
+        assert_eq!(per_shard.iter().sum::<usize>(), 4000);
+        assert!(per_shard.iter().all(|&n| n > 0));
+    }
+
+    #[test]
+    fn test_guards() {
+        let map = ShardedHashMap::new();
+        map.insert(String::from("hits"), 0);
+        *map.get_mut("hits").unwrap() += 5;
+        {
+            let hits = map.get("hits").unwrap();
+            assert_eq!((hits.key().as_str(), *hits), ("hits", 5));
+        }
+        assert_eq!(*map.get("hits").unwrap(), 5);
+        for i in 0..100 {
+            map.insert(i.to_string(), i);
+        }
+        assert!(map.get("misses").is_none());
+        assert_eq!(map.insert(String::from("hits"), 6), Some(5));
+
+        let total: usize = map.into_shards().iter().map(HashMap::len).sum();
+        assert_eq!(total, 101);
+    }
+
+    #[test]
+    fn test_downgrade_and_iter() {
+        let map = ShardedHashMap::with_shards(4);
+        for i in 0..50u32 {
+            map.insert(i, i);
+        }
+        let mut value = map.get_mut(&7).unwrap();
+        *value = 700;
+        let value = value.downgrade();
+        // Readers on other threads can join once the lock is downgraded.
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(*map.get(&7).unwrap(), 700));
+        });
+        assert_eq!((*value.key(), *value), (7, 700));
+        drop(value);
+
+        let mut seen: Vec<(u32, u32)> = map.iter().map(|entry| (*entry.key(), *entry)).collect();
+        seen.sort_unstable();
+        assert_eq!(seen.len(), 50);
+        assert_eq!(seen[7], (7, 700));
+
+        // Items outlive the iterator, each keeping its shard readable.
+        let held: Vec<_> = map.iter().filter(|entry| *entry.key() < 3).collect();
+        let mut keys: Vec<u32> = held.iter().map(|entry| **entry).collect();
+        drop(held);
+        keys.sort_unstable();
+        assert_eq!(keys, [0, 1, 2]);
+        map.insert(100, 100);
+        assert_eq!(map.iter().count(), 51);
+    }
+
+    #[test]
+    fn test_entry() {
+        let map = ShardedHashMap::with_shards(4);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                let map = &map;
+                scope.spawn(move || {
+                    for i in 0..1000u32 {
+                        *map.entry(i % 10).or_insert(0) += 1;
+                    }
+                });
+            }
+        });
+        assert!((0..10).all(|i| *map.get(&i).unwrap() == 800));
+
+        assert_eq!(*map.entry(3).and_modify(|v| *v = 0).or_insert(5), 0);
+        assert_eq!(*map.entry(20).and_modify(|v| *v = 0).or_insert(5), 5);
+        assert_eq!(map.entry(21).or_default().downgrade().key(), &21);
+        match map.entry(4) {
+            ShardedEntry::Occupied(mut entry) => {
+                assert_eq!(entry.insert(1), 800);
+                assert_eq!(entry.remove_entry(), (4, 1));
+            }
+            ShardedEntry::Vacant(_) => unreachable!(),
+        }
+        match map.entry(4) {
+            ShardedEntry::Vacant(entry) => assert_eq!(entry.into_key(), 4),
+            ShardedEntry::Occupied(_) => unreachable!(),
+        }
+        assert!(!map.contains_key(&4));
+        assert_eq!(map.len(), 11);
+    }
+
+    #[test]
+    fn test_stats_and_reshard() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone)]
+        struct Counting(Arc<AtomicUsize>, RandomState);
+
+        impl BuildHasher for Counting {
+            type Hasher = <RandomState as BuildHasher>::Hasher;
+
+            fn build_hasher(&self) -> Self::Hasher {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                self.1.build_hasher()
+            }
+        }
+
+        let hashes = Arc::new(AtomicUsize::new(0));
+        let mut map = ShardedHashMap::with_shards_and_hasher(2, Counting(Arc::clone(&hashes), RandomState::new()));
+        for i in 0..1000u32 {
+            map.insert(i, i);
+        }
+        let stats = map.shard_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats.iter().map(|shard| shard.len).sum::<usize>(), 1000);
+        for shard in &stats {
+            assert!(shard.len <= shard.capacity && shard.capacity < shard.buckets);
+            assert!(shard.occupancy() > 0.0 && shard.occupancy() < 1.0);
+        }
+
+        let before = hashes.load(Ordering::SeqCst);
+        map.reshard(12);
+        assert_eq!(hashes.load(Ordering::SeqCst), before);
+        assert_eq!(map.shard_count(), 16);
+        let stats = map.shard_stats();
+        assert_eq!(stats.iter().map(|shard| shard.len).sum::<usize>(), 1000);
+        assert!(stats.iter().all(|shard| shard.len > 0 && shard.len < 1000 / 4));
+        assert!((0..1000).all(|i| *map.get(&i).unwrap() == i));
+
+        map.reshard(1);
+        assert_eq!(map.shard_stats()[0].len, 1000);
+    }
+
+    #[test]
+    fn test_bulk_get() {
+        let map = ShardedHashMap::with_shards(4);
+        for i in 0..100u32 {
+            map.insert(i, i * 2);
+        }
+        let keys: Vec<u32> = (90..110).rev().chain(0..5).collect();
+        let refs: Vec<&u32> = keys.iter().collect();
+        let values = map.bulk_get(&refs);
+        assert_eq!(values.len(), keys.len());
+        for (key, value) in keys.iter().zip(&values) {
+            assert_eq!(*value, if *key < 100 { Some(key * 2) } else { None });
+        }
+        assert!(map.bulk_get::<u32>(&[]).is_empty());
+
+        // Repeated and missing keys, with several keys per shard.
+        let map = ShardedHashMap::with_shards(2);
+        map.insert("a", 1);
+        map.insert("b", 2);
+        assert_eq!(map.bulk_get(&["b", "a", "b", "c"]), [Some(2), Some(1), Some(2), None]);
+    }
+}
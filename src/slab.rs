@@ -0,0 +1,160 @@
+use std::hash::Hash;
+
+use crate::{Equivalent, HashMap};
+
+/// Refers to a value in a [`SlabHashMap`] for as long as its key stays in the
+/// map, regardless of inserts or resizes in between.
+///
+/// Once the key is removed the handle is dead for good: its slot may be
+/// reused, but a generation counter keeps the old handle from resolving to
+/// the new value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueHandle {
+    index: usize,
+    generation: u64,
+}
+
+struct Slot<V> {
+    generation: u64,
+    value: Option<V>,
+}
+
+/// Map whose values live in a slab while the hash table only stores handles
+/// into it, so a [`ValueHandle`] keeps finding its value however the table
+/// changes. Addresses are not stable: the slab is a `Vec` and moves its
+/// values when it grows, so keep handles across inserts, not references.
+/// [`StableHashMap`](crate::StableHashMap) is the one for stable `&V`s.
+pub struct SlabHashMap<K, V> {
+    index: HashMap<K, ValueHandle>,
+    slots: Vec<Slot<V>>,
+    free: Vec<usize>,
+}
+
+impl<K, V> Default for SlabHashMap<K, V>
+    where K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> SlabHashMap<K, V>
+    where K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        SlabHashMap {
+            index: HashMap::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Inserts or overwrites the value for `key`. Overwriting keeps the
+    /// slot, so existing handles see the new value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(handle) = self.value_handle(&key) {
+            return self.slots[handle.index].value.replace(value);
+        }
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index].value = Some(value);
+                index
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                self.slots.len() - 1
+            }
+        };
+        let generation = self.slots[index].generation;
+        self.index.insert(key, ValueHandle { index, generation });
+        None
+    }
+
+    pub fn remove<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let handle = self.index.remove(key)?;
+        let slot = &mut self.slots[handle.index];
+        slot.generation += 1;
+        self.free.push(handle.index);
+        slot.value.take()
+    }
+
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        self.get_by_handle(self.value_handle(key)?)
+    }
+
+    pub fn get_mut<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<&mut V> {
+        let handle = self.value_handle(key)?;
+        self.get_by_handle_mut(handle)
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.value_handle(key).is_some()
+    }
+
+    pub fn value_handle<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<ValueHandle> {
+        self.index.get(key).copied()
+    }
+
+    /// Returns `None` if the handle's key has since been removed.
+    pub fn get_by_handle(&self, handle: ValueHandle) -> Option<&V> {
+        let slot = self.slots.get(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    pub fn get_by_handle_mut(&mut self, handle: ValueHandle) -> Option<&mut V> {
+        let slot = self.slots.get_mut(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handles_survive_growth() {
+        let keys: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+        let mut map = SlabHashMap::new();
+        map.insert("first", 1);
+        let handle = map.value_handle(&"first").unwrap();
+
+        for key in &keys {
+            map.insert(key.as_str(), 0);
+        }
+        assert_eq!(map.get_by_handle(handle), Some(&1));
+
+        map.insert("first", 2);
+        *map.get_by_handle_mut(handle).unwrap() += 1;
+        assert_eq!(map.get(&"first"), Some(&3));
+    }
+
+    #[test]
+    fn test_stale_handles() {
+        let mut map = SlabHashMap::new();
+        map.insert("a", 'a');
+        let handle = map.value_handle(&"a").unwrap();
+        assert_eq!(map.remove(&"a"), Some('a'));
+        assert_eq!(map.get_by_handle(handle), None);
+
+        map.insert("b", 'b');
+        let reused = map.value_handle(&"b").unwrap();
+        assert_ne!(reused, handle);
+        assert_eq!(map.get_by_handle(handle), None);
+        assert_eq!(map.get_by_handle(reused), Some(&'b'));
+        assert_eq!(map.len(), 1);
+    }
+}
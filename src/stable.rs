@@ -0,0 +1,218 @@
+use std::cell::{Cell, UnsafeCell};
+use std::hash::Hash;
+use std::ptr::NonNull;
+
+use crate::{Equivalent, HashMap};
+
+/// Map that gives every value its own allocation, so references handed out
+/// by [`get`] and [`insert`] stay valid across later inserts.
+///
+/// Growing the table only moves the pointers to the values, never the
+/// values themselves, so `insert` can take `&self`. Anything that could drop or move a value —
+/// overwriting, removing, handing out `&mut V` — takes `&mut self`, so the
+/// borrow checker rules it out while any `&V` is alive:
+///
+/// ```
+/// let map = hashmap::StableHashMap::new();
+/// let first = map.insert(0, String::from("first"));
+/// for i in 1..1000 {
+///     map.insert(i, i.to_string());
+/// }
+/// assert_eq!(first, "first");
+/// ```
+///
+/// [`get`]: StableHashMap::get
+/// [`insert`]: StableHashMap::insert
+pub struct StableHashMap<K, V> {
+    // Raw pointers rather than `Box`es: moving a `Box` asserts unique
+    // access to its contents, which the `&V`s handed out would violate.
+    map: UnsafeCell<HashMap<K, NonNull<V>>>,
+    in_use: Cell<bool>,
+}
+
+// SAFETY: the map owns the values behind its pointers, as it would boxes.
+unsafe impl<K: Send, V: Send> Send for StableHashMap<K, V> {}
+
+impl<K, V> Drop for StableHashMap<K, V> {
+    fn drop(&mut self) {
+        for &value in self.map.get_mut().values() {
+            // SAFETY: every pointer in the map came from `into_raw`, and
+            // none is used again once the map is gone.
+            drop(unsafe { from_raw(value) });
+        }
+    }
+}
+
+impl<K, V> Default for StableHashMap<K, V>
+    where K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> StableHashMap<K, V>
+    where K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        StableHashMap {
+            map: UnsafeCell::new(HashMap::new()),
+            in_use: Cell::new(false),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.with_map(|map| map.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Inserts `value` unless `key` is already present, and returns the
+    /// value now stored under `key`. An existing value is never replaced
+    /// through a shared reference; `key` and `value` are dropped instead.
+    pub fn insert(&self, key: K, value: V) -> &V {
+        let (value, rejected) = self.with_map(|map| {
+            let hash = map.hash(&key);
+            match map.find_hashed(hash, &key) {
+                Some(index) => (map.table.get(index).1, Some((key, value))),
+                None => {
+                    map.prepare_insert();
+                    let index = map.push_new(hash, key, into_raw(value));
+                    (map.table.get(index).1, None)
+                }
+            }
+        });
+        // Dropped outside `with_map`, since their `Drop` may use the map.
+        drop(rejected);
+        // SAFETY: the value has its own allocation, which is only freed or
+        // written through `&mut self`, and that can't coexist with the
+        // returned borrow of `self`.
+        unsafe { value.as_ref() }
+    }
+
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        let value = self.with_map(|map| {
+            let index = map.find(key)?;
+            Some(map.table.get(index).1)
+        })?;
+        // SAFETY: as in `insert`.
+        Some(unsafe { value.as_ref() })
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get_mut<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<&mut V> {
+        let map = self.map.get_mut();
+        let index = map.find(key)?;
+        // SAFETY: `&mut self` rules out any other reference to the value.
+        Some(unsafe { &mut *map.table.get(index).1.as_ptr() })
+    }
+
+    /// Inserts or overwrites, like `HashMap::insert`.
+    pub fn insert_mut(&mut self, key: K, value: V) -> Option<V> {
+        let old = self.map.get_mut().insert(key, into_raw(value))?;
+        // SAFETY: `old` is out of the map, and `&mut self` rules out any
+        // reference to it.
+        Some(unsafe { from_raw(old) })
+    }
+
+    pub fn remove<Q: Hash + Equivalent<K> + ?Sized>(&mut self, key: &Q) -> Option<V> {
+        let old = self.map.get_mut().remove(key)?;
+        // SAFETY: as in `insert_mut`.
+        Some(unsafe { from_raw(old) })
+    }
+
+    pub fn into_inner(mut self) -> HashMap<K, Box<V>> {
+        let map = std::mem::take(self.map.get_mut());
+        let mut boxed = HashMap::with_capacity(map.len());
+        // SAFETY: each pointer came from `into_raw` and is taken out of the
+        // map, which is emptied so that `Drop` frees nothing.
+        boxed.extend(map.into_iter().map(|(key, value)| (key, unsafe { Box::from_raw(value.as_ptr()) })));
+        boxed
+    }
+
+    // Gives `f` exclusive access to the inner map. `K`'s `Hash` and `Eq`
+    // run inside `f`; if they reach back into this map through a shared
+    // reference we panic rather than alias the `&mut`.
+    fn with_map<R>(&self, f: impl FnOnce(&mut HashMap<K, NonNull<V>>) -> R) -> R {
+        struct Release<'a>(&'a Cell<bool>);
+
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+
+        assert!(!self.in_use.replace(true), "StableHashMap accessed re-entrantly");
+        let _release = Release(&self.in_use);
+        // SAFETY: `in_use` guarantees this is the only live reference
+        // derived from the cell through `&self`, and `&mut self` methods
+        // can't run while `&self` is borrowed.
+        f(unsafe { &mut *self.map.get() })
+    }
+}
+
+fn into_raw<V>(value: V) -> NonNull<V> {
+    // SAFETY: `Box::into_raw` never returns null.
+    unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(value))) }
+}
+
+// SAFETY: `value` must have come from `into_raw`, and must not be used
+// again.
+unsafe fn from_raw<V>(value: NonNull<V>) -> V {
+    *Box::from_raw(value.as_ptr())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references_survive_inserts() {
+        let map = StableHashMap::new();
+        let first = map.insert(0, String::from("zero"));
+        let refs: Vec<&String> = (1..500).map(|i| map.insert(i, i.to_string())).collect();
+        assert_eq!(first, "zero");
+        assert_eq!(refs[41], "42");
+        assert_eq!(map.get(&499).map(String::as_str), Some("499"));
+        assert_eq!(map.len(), 500);
+    }
+
+    #[test]
+    fn test_insert_keeps_existing() {
+        let mut map = StableHashMap::new();
+        assert_eq!(map.insert("k", 1), &1);
+        assert_eq!(map.insert("k", 2), &1);
+
+        assert_eq!(map.insert_mut("k", 3), Some(1));
+        *map.get_mut(&"k").unwrap() += 1;
+        assert_eq!(map.get(&"k"), Some(&4));
+        assert_eq!(map.remove(&"k"), Some(4));
+        assert!(map.is_empty());
+    }
+
+    // Run under `cargo +nightly miri test stable` to check that resizing
+    // leaves the references valid.
+    #[test]
+    fn test_reference_across_resize() {
+        let map = StableHashMap::new();
+        let held = map.insert(0, vec![0u8; 4]);
+        let slots = map.with_map(|map| map.table.slots());
+        for i in 1..64 {
+            map.insert(i, vec![i as u8; 4]);
+        }
+        assert!(map.with_map(|map| map.table.slots()) > slots);
+        assert_eq!(held, &[0; 4]);
+        assert_eq!(map.get(&63), Some(&vec![63; 4]));
+
+        let mut map = map;
+        assert_eq!(map.insert_mut(1, Vec::new()), Some(vec![1; 4]));
+        let inner = map.into_inner();
+        assert_eq!(inner.len(), 64);
+        assert_eq!(*inner[&0], [0; 4]);
+    }
+}
@@ -0,0 +1,130 @@
+use std::hash::Hash;
+use std::sync::OnceLock;
+
+use crate::{Equivalent, HashMap, Iter};
+
+/// A read-only map built on first access, for use in a `static`.
+///
+/// ```
+/// use hashmap::{HashMap, StaticHashMap};
+///
+/// static PORTS: StaticHashMap<&str, u16> = StaticHashMap::new(|| {
+///     let mut map = HashMap::new();
+///     map.insert("http", 80);
+///     map.insert("https", 443);
+///     map
+/// });
+///
+/// assert_eq!(PORTS.get(&"https"), Some(&443));
+/// ```
+///
+/// [`static_hash_map!`] declares the same thing from a list of entries.
+pub struct StaticHashMap<K, V> {
+    map: OnceLock<HashMap<K, V>>,
+    init: fn() -> HashMap<K, V>,
+}
+
+impl<K, V> StaticHashMap<K, V> {
+    pub const fn new(init: fn() -> HashMap<K, V>) -> Self {
+        StaticHashMap {
+            map: OnceLock::new(),
+            init,
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> StaticHashMap<K, V> {
+    /// Builds the map if this is the first access, then returns it.
+    pub fn force(&self) -> &HashMap<K, V> {
+        self.map.get_or_init(self.init)
+    }
+
+    pub fn get<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> Option<&V> {
+        self.force().get(key)
+    }
+
+    pub fn contains_key<Q: Hash + Equivalent<K> + ?Sized>(&self, key: &Q) -> bool {
+        self.force().contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.force().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.force().is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.force().into_iter()
+    }
+}
+
+impl<'a, K: Hash + Eq, V> IntoIterator for &'a StaticHashMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Declares a [`StaticHashMap`] from a list of entries.
+///
+/// ```
+/// hashmap::static_hash_map! {
+///     static STATUS: StaticHashMap<u16, &'static str> = {
+///         200 => "OK",
+///         404 => "Not Found",
+///     };
+/// }
+///
+/// assert_eq!(STATUS.get(&404), Some(&"Not Found"));
+/// ```
+#[macro_export]
+macro_rules! static_hash_map {
+    (
+        $(#[$meta:meta])*
+        $vis:vis static $name:ident: StaticHashMap<$K:ty, $V:ty> = {
+            $($key:expr => $value:expr),* $(,)?
+        };
+    ) => {
+        $(#[$meta])*
+        $vis static $name: $crate::StaticHashMap<$K, $V> = $crate::StaticHashMap::new(|| {
+            let mut map = $crate::HashMap::new();
+            $(map.insert($key, $value);)*
+            map
+        });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    static_hash_map! {
+        static KEYWORDS: StaticHashMap<&'static str, u8> = {
+            "fn" => 1,
+            "let" => 2,
+            "match" => 3,
+        };
+    }
+
+    #[test]
+    fn test_static_map() {
+        assert_eq!(KEYWORDS.len(), 3);
+        assert_eq!(KEYWORDS.get(&"let"), Some(&2));
+        assert!(!KEYWORDS.contains_key(&"loop"));
+        assert_eq!(KEYWORDS.iter().map(|(_, &v)| v as u32).sum::<u32>(), 6);
+    }
+
+    #[test]
+    fn test_static_map_across_threads() {
+        let handles: Vec<_> = (0..4)
+            .map(|_| thread::spawn(|| KEYWORDS.get(&"match").copied()))
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), Some(3));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Weak};
+
+use crate::HashMap;
+
+const MIN_PRUNE_INTERVAL: usize = 16;
+
+// Identifies a key by the address of its allocation. A `Weak` keeps that
+// allocation reserved even after the key is dropped, so the address can't be
+// reused by another `Arc` while the entry exists.
+struct WeakKey<K: ?Sized>(Weak<K>);
+
+impl<K: ?Sized> Hash for WeakKey<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0.as_ptr() as *const () as usize).hash(state);
+    }
+}
+
+impl<K: ?Sized> PartialEq for WeakKey<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
+
+impl<K: ?Sized> Eq for WeakKey<K> {}
+
+/// Map from objects (by identity) to values that doesn't keep the objects
+/// alive. Once the last `Arc` to a key is dropped its entry becomes
+/// unreachable, and it is cleared out by [`prune`](Self::prune), which
+/// `insert` also runs periodically.
+pub struct WeakKeyHashMap<K: ?Sized, V> {
+    map: HashMap<WeakKey<K>, V>,
+    inserts_since_prune: usize,
+}
+
+impl<K: ?Sized, V> Default for WeakKeyHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: ?Sized, V> WeakKeyHashMap<K, V> {
+    pub fn new() -> Self {
+        WeakKeyHashMap {
+            map: HashMap::new(),
+            inserts_since_prune: 0,
+        }
+    }
+
+    /// Number of entries, including ones whose key has died but that haven't
+    /// been pruned yet.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn insert(&mut self, key: &Arc<K>, value: V) -> Option<V> {
+        self.inserts_since_prune += 1;
+        if self.inserts_since_prune >= self.map.len().max(MIN_PRUNE_INTERVAL) {
+            self.prune();
+        }
+        self.map.insert(WeakKey(Arc::downgrade(key)), value)
+    }
+
+    pub fn get(&self, key: &Arc<K>) -> Option<&V> {
+        self.map.get(&WeakKey(Arc::downgrade(key)))
+    }
+
+    pub fn contains_key(&self, key: &Arc<K>) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &Arc<K>) -> Option<V> {
+        self.map.remove(&WeakKey(Arc::downgrade(key)))
+    }
+
+    /// Drops every entry whose key no longer has a strong reference,
+    /// returning how many were removed.
+    pub fn prune(&mut self) -> usize {
+        let before = self.map.len();
+        self.map.retain(|key, _| key.0.strong_count() > 0);
+        self.inserts_since_prune = 0;
+        before - self.map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_keys() {
+        let a = Arc::new(String::from("a"));
+        let b = Arc::new(String::from("a"));
+        let mut map = WeakKeyHashMap::new();
+        map.insert(&a, 1);
+        map.insert(&b, 2);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&a), Some(&1));
+        assert_eq!(map.get(&b), Some(&2));
+
+        drop(a);
+        assert_eq!(map.prune(), 1);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove(&b), Some(2));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_periodic_prune() {
+        let mut map = WeakKeyHashMap::<str, usize>::new();
+        for i in 0..1000 {
+            let key: Arc<str> = Arc::from(i.to_string());
+            map.insert(&key, i);
+        }
+        assert!(map.len() < 100);
+
+        let keep: Arc<str> = Arc::from("keep");
+        map.insert(&keep, 7);
+        let before = map.len();
+        assert_eq!(map.prune(), before - 1);
+        assert_eq!(map.get(&keep), Some(&7));
+    }
+}